@@ -19,12 +19,26 @@ pub fn div_for_debugging<'a>(rrc: &'a RootRenderingComponent, bump: &'a Bump) ->
     let text2 = bumpalo::format!(in bump, "debug: status: {}, ws_uid: {}",
     rrc.game_data.game_status,rrc.game_data.my_ws_uid)
     .into_bump_str();
+    //the last msg rejected by the server
+    let text3 = bumpalo::format!(in bump, "protocol error: {}",
+    rrc.game_data.protocol_error_text)
+    .into_bump_str();
+    //the last game state that differed from the server
+    let text4 = bumpalo::format!(in bump, "desync: {}",
+    rrc.game_data.desync_text)
+    .into_bump_str();
 
     dodrio!(bump,
     <div>
         <h4>
             {vec![text(text2)]}
         </h4>
+        <h4>
+            {vec![text(text3)]}
+        </h4>
+        <h4>
+            {vec![text(text4)]}
+        </h4>
         {button_for_fullscreen(bump)}
     </div>
     )
@@ -2,7 +2,7 @@
 //! and most important the on click event
 
 //region: use, const
-use crate::gamedata::{CardStatusCardFace, Size2d};
+use crate::gamedata::Size2d;
 use crate::rootrenderingcomponent::RootRenderingComponent;
 use crate::statusplaybefore1stcard;
 use crate::statusplaybefore2ndcard;
@@ -12,7 +12,7 @@ use crate::rootrenderingcomponent;
 use conv::{ConvUtil};
 use dodrio::bumpalo::{self, Bump};
 use dodrio::Node;
-use mem4_common::GameStatus;
+use mem4_common::{CardStatusCardFace, GameStatus};
 use typed_html::dodrio;
 //use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
@@ -40,10 +40,12 @@ where
             </div>
         )
     } else {
+        //the background of the grid is tinted with my color
         let xstyle = format!(
-            "width:{}px; height:{}px;grid-template-columns: {} {} {} {};",
+            "width:{}px; height:{}px;background-color:{};grid-template-columns: {} {} {} {};",
             max_grid_size.hor,
             max_grid_size.ver,
+            rrc.game_data.my_color,
             if unwrap!(rrc.game_data.game_config.as_ref()).grid_items_hor >= 1 {
                 "auto"
             } else {
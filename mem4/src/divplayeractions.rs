@@ -10,7 +10,6 @@ use crate::statustaketurnbegin;
 use crate::statusinviteaskbegin;
 use crate::statusinviteasked;
 use crate::statusinviteasking;
-use crate::websocketreconnect;
 
 use dodrio::builder::text;
 use dodrio::bumpalo::{self, Bump};
@@ -27,12 +26,8 @@ pub fn div_player_actions_from_game_status<'a, 'bump>(
 where
     'a: 'bump,
 {
-    if !rrc.game_data.is_status_invite_ask_begin()
-        && (rrc.game_data.is_reconnect || rrc.game_data.ws.ready_state() != 1)
-    {
-        //ready_state: 0	CONNECTING, 1	OPEN, 2	CLOSING, 3	CLOSED
-        websocketreconnect::div_reconnect(rrc, bump)
-    } else if let GameStatus::InviteAskBegin = rrc.game_data.game_status {
+    //while reconnecting the game stays usable. The msgs wait in the outbox.
+    if let GameStatus::InviteAskBegin = rrc.game_data.game_status {
         statusinviteaskbegin::div_invite_ask_begin(rrc, bump)
     } else if let GameStatus::InviteAsked = rrc.game_data.game_status {
         statusinviteasked::div_invite_asked(rrc, bump)
@@ -53,6 +48,25 @@ where
     }
 }
 
+///render the info text for the player. The InviteAskBegin status renders it by itself.
+pub fn div_info_text<'a, 'bump>(
+    rrc: &'a RootRenderingComponent,
+    bump: &'bump Bump,
+) -> Vec<Node<'bump>>
+where
+    'a: 'bump,
+{
+    let mut vec_of_nodes = Vec::new();
+    if rrc.game_data.info_text != "" && !rrc.game_data.is_status_invite_ask_begin() {
+        vec_of_nodes.push(dodrio!(bump,
+        <h3 style= "color:red;">
+            {vec![text(bumpalo::format!(in bump, "{}", rrc.game_data.info_text).into_bump_str())]}
+        </h3>
+        ));
+    }
+    vec_of_nodes
+}
+
 ///render unpredicted
 fn div_unpredicted<'a, 'bump>(
     root_rendering_component: &'a RootRenderingComponent,
@@ -0,0 +1,117 @@
+//! divplayerprofile.rs - renders the div to choose my name, color and avatar
+//! The profile is saved in the local storage and sent with the invite, the accept and the game data.
+
+//region: use
+use crate::rootrenderingcomponent::RootRenderingComponent;
+
+use mem4_common::PLAYER_COLORS;
+use dodrio::builder::text;
+use dodrio::bumpalo::{self, Bump};
+use dodrio::Node;
+use typed_html::dodrio;
+use wasm_bindgen::JsCast;
+//endregion
+
+///render the input for the name, the colors and the avatars from the content pack
+pub fn div_player_profile<'a, 'bump>(
+    rrc: &'a RootRenderingComponent,
+    bump: &'bump Bump,
+) -> Node<'bump>
+where
+    'a: 'bump,
+{
+    let mut vec_of_nodes = Vec::new();
+    vec_of_nodes.push(dodrio!(bump,
+    <div>
+        <h4>
+            {vec![text(bumpalo::format!(in bump, "My name:{}", "").into_bump_str())]}
+        </h4>
+        <input id="input_player_name" type="text" maxlength="12" style="font-size:24px;width:200px;"
+        value={bumpalo::format!(in bump, "{}", rrc.game_data.my_name).into_bump_str()}>
+        </input>
+    </div>
+    ));
+    let mut color_nodes = Vec::new();
+    for &color in &PLAYER_COLORS {
+        let class = if color == rrc.game_data.my_color {
+            "profile_color profile_selected"
+        } else {
+            "profile_color"
+        };
+        let style = bumpalo::format!(in bump, "background-color:{};", color).into_bump_str();
+        color_nodes.push(dodrio!(bump,
+        <div class={class} style={style} onclick={move |root, vdom, _event| {
+                let rrc = root.unwrap_mut::<RootRenderingComponent>();
+                rrc.game_data.my_color = color.to_string();
+                save_my_profile(rrc);
+                vdom.schedule_render();
+                }}>
+        </div>
+        ));
+    }
+    vec_of_nodes.push(dodrio!(bump,
+    <div class="profile_choices">
+        {color_nodes}
+    </div>
+    ));
+    //the avatars are the card images of the content pack
+    if let Some(game_config) = rrc.game_data.game_config.as_ref() {
+        let mut avatar_nodes = Vec::new();
+        for avatar in game_config.avatars(&rrc.game_data.asked_folder_name) {
+            let class = if avatar == rrc.game_data.my_avatar {
+                "profile_avatar profile_selected"
+            } else {
+                "profile_avatar"
+            };
+            let src = bumpalo::format!(in bump, "{}", avatar).into_bump_str();
+            avatar_nodes.push(dodrio!(bump,
+            <div class={class} onclick={move |root, vdom, _event| {
+                    let rrc = root.unwrap_mut::<RootRenderingComponent>();
+                    rrc.game_data.my_avatar = avatar.clone();
+                    save_my_profile(rrc);
+                    vdom.schedule_render();
+                    }}>
+                <img src={src}>
+                </img>
+            </div>
+            ));
+        }
+        vec_of_nodes.push(dodrio!(bump,
+        <div class="profile_choices">
+            {avatar_nodes}
+        </div>
+        ));
+    }
+    dodrio!(bump,
+    <div>
+        {vec_of_nodes}
+    </div>
+    )
+}
+
+///read my name from the input element, if it is rendered, and save the profile
+pub fn save_my_profile(rrc: &mut RootRenderingComponent) {
+    let window = unwrap!(web_sys::window(), "error: web_sys::window");
+    let document = unwrap!(window.document(), "error: window.document");
+    if let Some(input_player_name) = document
+        .get_element_by_id("input_player_name")
+        .and_then(|element| element.dyn_into::<web_sys::HtmlInputElement>().ok())
+    {
+        rrc.game_data.my_name = input_player_name.value().trim().to_string();
+    }
+    rrc.game_data.save_my_profile();
+    rrc.check_invalidate_for_all_components();
+}
+
+///the small avatar img of a player, empty if he has not chosen one
+pub fn img_avatar<'bump>(bump: &'bump Bump, avatar: &str) -> Vec<Node<'bump>> {
+    let mut vec_of_nodes = Vec::new();
+    if avatar != "" {
+        let src = bumpalo::format!(in bump, "{}", avatar).into_bump_str();
+        vec_of_nodes.push(dodrio!(bump,
+        <img class="avatar_small" src={src}>
+        </img>
+        ));
+    }
+    vec_of_nodes
+}
@@ -2,23 +2,44 @@
 
 //region: use
 use crate::gamedata::GameData;
+use crate::divplayerprofile;
 //use crate::logmod;
 
-use dodrio::builder::{text};
+use dodrio::builder::text;
 use dodrio::bumpalo::{self, Bump};
 use dodrio::{Node, Render};
+use mem4_common::BEST_OF_ROUNDS;
 use typed_html::dodrio;
 //endregion
 
-///Render Component: player score
+///the score of one player in the cache of the scoreboard
+#[derive(PartialEq, Clone)]
+struct PlayerScore {
+    ///points of this round
+    points: usize,
+    ///points of the finished rounds of the series
+    total_points: usize,
+    ///the player has lost the connection
+    is_absent: bool,
+    ///display name
+    name: String,
+    ///css color of the player
+    color: String,
+    ///img src of the avatar
+    avatar: String,
+}
+
+///Render Component: the scoreboard of all the players
 ///Its private fields are a cache copy from `game_data` fields.
 ///They are used for rendering
 ///and for checking if the data has changed to invalidate the render cache.
 pub struct PlayersAndScores {
-    ///whose turn is now:  player 1 or 2
+    ///whose turn is now:  player 1,2,3,...
     player_turn: usize,
-    ///my points
-    my_points: usize,
+    ///the scores of all the players in the order of the turns
+    player_scores: Vec<PlayerScore>,
+    ///round of the best-of series
+    round: usize,
     ///What player am I
     my_player_number: usize,
     ///my ws client instance unique id.
@@ -29,7 +50,8 @@ impl PlayersAndScores {
     ///constructor
     pub const fn new(my_ws_uid: usize) -> Self {
         PlayersAndScores {
-            my_points: 0,
+            player_scores: Vec::new(),
+            round: 0,
             my_player_number: 1,
             player_turn: 0,
             my_ws_uid,
@@ -38,34 +60,23 @@ impl PlayersAndScores {
     ///copies the data from game data to internal cache
     /// internal fiels are used to render component
     pub fn update_intern_cache(&mut self, game_data: &GameData) -> bool {
-        /*
-        logmod::log1_str(&format!(
-            "update_intern_cache  my_player_number {}",
-            &game_data.my_player_number
-        ));
-        */
-
         let mut is_invalidated;
         is_invalidated = false;
-        if game_data.my_player_number > 0
-            && !game_data.players.is_empty()
-            && game_data.players.len() >= unwrap!(game_data.my_player_number.checked_sub(1))
-            && self.my_points
-                != unwrap!(
-                    game_data
-                        .players
-                        .get(unwrap!(game_data.my_player_number.checked_sub(1))),
-                    "game_data.players.get(game_data.my_player_number - 1)"
-                )
-                .points
-        {
-            self.my_points = unwrap!(
-                game_data
-                    .players
-                    .get(unwrap!(game_data.my_player_number.checked_sub(1))),
-                "game_data.players.get(game_data.my_player_number - 1)"
-            )
-            .points;
+        let player_scores: Vec<PlayerScore> = game_data
+            .players
+            .iter()
+            .enumerate()
+            .map(|(index, p)| PlayerScore {
+                points: p.points,
+                total_points: p.total_points,
+                is_absent: p.is_absent,
+                name: p.display_name(unwrap!(index.checked_add(1))),
+                color: p.color.clone(),
+                avatar: p.avatar.clone(),
+            })
+            .collect();
+        if self.player_scores != player_scores {
+            self.player_scores = player_scores;
             is_invalidated = true;
         }
         if self.my_player_number != game_data.my_player_number {
@@ -80,29 +91,61 @@ impl PlayersAndScores {
             self.my_ws_uid = game_data.my_ws_uid;
             is_invalidated = true;
         }
+        if self.round != game_data.round {
+            self.round = game_data.round;
+            is_invalidated = true;
+        }
         is_invalidated
     }
 }
 
 impl Render for PlayersAndScores {
     ///This rendering will be rendered and then cached . It will not be rerendered untill invalidation.
-    ///It is invalidate, when the points change.
-    ///html element with 1 score for this players
+    ///It is invalidate, when the points, the turn, the profiles or the absent players change.
+    ///html element with one row for every player. The player in turn is highlighted.
     fn render<'a, 'bump>(&'a self, bump: &'bump Bump) -> Node<'bump>
     where
         'a: 'bump,
     {
-        let text1 = bumpalo::format!(in bump, "Player{}: {} points",
-        self.my_player_number, self.my_points)
-        .into_bump_str();
+        let mut vec_of_nodes = Vec::new();
+        if self.round > 1 {
+            vec_of_nodes.push(dodrio!(bump,
+            <div class= "grid_item">
+                {vec![text(bumpalo::format!(in bump, "Round {} of {}", self.round, BEST_OF_ROUNDS).into_bump_str())]}
+            </div>
+            ));
+        }
+        for (index, score) in self.player_scores.iter().enumerate() {
+            let player_number = unwrap!(index.checked_add(1));
+            let class = match (score.is_absent, player_number == self.player_turn) {
+                (true, true) => "grid_item player_absent player_turn",
+                (true, false) => "grid_item player_absent",
+                (false, true) => "grid_item player_turn",
+                (false, false) => "grid_item",
+            };
+            let text1 = bumpalo::format!(in bump, "{}{}: {} points{}{}",
+                score.name,
+                if player_number == self.my_player_number { " (me)" } else { "" },
+                score.points,
+                if self.round > 1 { format!(", total {}", score.total_points) } else { "".to_string() },
+                if score.is_absent { ", absent" } else { "" })
+            .into_bump_str();
+            let style = if score.color == "" {
+                ""
+            } else {
+                bumpalo::format!(in bump, "border-left: 8px solid {};", score.color).into_bump_str()
+            };
+            vec_of_nodes.push(dodrio!(bump,
+            <div class={class} style={style}>
+                {divplayerprofile::img_avatar(bump, &score.avatar)}
+                {vec![text(text1)]}
+            </div>
+            ));
+        }
         //return
         dodrio!(bump,
         <div class="grid_container_players" style= "grid-template-columns: auto;">
-            <div class= "grid_item" style="text-align: center;">
-                {vec![
-                    text(text1),
-                ]}
-            </div>
+            {vec_of_nodes}
         </div>
         )
     }
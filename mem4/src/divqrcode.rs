@@ -0,0 +1,83 @@
+//! divqrcode.rs - renders the QR code with the join link of my room
+//! The other phones scan it and go straight to the accept screen.
+
+//region: use
+use crate::gamedata::GameData;
+use crate::logmod;
+use crate::rootrenderingcomponent::RootRenderingComponent;
+
+use dodrio::builder::text;
+use dodrio::bumpalo::{self, Bump};
+use dodrio::Node;
+use qrcode::render::svg;
+use qrcode::QrCode;
+use typed_html::dodrio;
+//endregion
+
+///the join link with the join code of my room and the content
+pub fn join_link(game_data: &GameData) -> String {
+    format!(
+        "{}?room={}&pack={}",
+        game_data.href, game_data.room_code, game_data.asked_folder_name
+    )
+}
+
+///build the QR code of the join link only when the join code or the content has changed.
+///Empty if I am not in a room.
+pub fn update_qr_code(game_data: &mut GameData) {
+    let join_link = if game_data.room_code == "" {
+        "".to_string()
+    } else {
+        join_link(game_data)
+    };
+    if join_link != game_data.qr_code_join_link {
+        game_data.qr_code_src = if join_link == "" {
+            "".to_string()
+        } else {
+            match QrCode::new(join_link.as_bytes()) {
+                Ok(code) => {
+                    let svg_xml = code
+                        .render::<svg::Color>()
+                        .min_dimensions(200, 200)
+                        .dark_color(svg::Color("#000000"))
+                        .light_color(svg::Color("#ffffff"))
+                        .build();
+                    //the svg goes in the src attribute as data url
+                    format!(
+                        "data:image/svg+xml;charset=utf-8,{}",
+                        String::from(js_sys::encode_uri_component(&svg_xml))
+                    )
+                }
+                Err(e) => {
+                    logmod::log1_str(&format!("QR code error: {}", e));
+                    "".to_string()
+                }
+            }
+        };
+        game_data.qr_code_join_link = join_link;
+    }
+}
+
+///render the cached QR code of the join link as svg image. Empty if I am not in a room.
+pub fn div_qr_code<'a, 'bump>(
+    rrc: &'a RootRenderingComponent,
+    bump: &'bump Bump,
+) -> Vec<Node<'bump>>
+where
+    'a: 'bump,
+{
+    let mut vec_of_nodes = Vec::new();
+    if rrc.game_data.qr_code_src != "" {
+        let src = rrc.game_data.qr_code_src.as_str();
+        vec_of_nodes.push(dodrio!(bump,
+        <div>
+            <img class="qr_code" src={src}>
+            </img>
+            <h4>
+                {vec![text(bumpalo::format!(in bump, "{}", rrc.game_data.qr_code_join_link).into_bump_str())]}
+            </h4>
+        </div>
+        ));
+    }
+    vec_of_nodes
+}
@@ -20,6 +20,7 @@ Put all the smartphones on the table near each other, so all players can see the
 them. It should look like a board game at this point.  
 The first player clicks on 'Invite for play?'.  
 He can choose different types of game visuals: alphabet, animal, playing cards,...  
+His screen shows the room code. The other players type this code and click 'Join room!'.  
 Other players then see on the screen 'Click here to Accept play!'.  
 Player1 sees how many players have accepted. Then he starts the game.  
 On the screen under the grid are clear signals which player plays and which waits.  
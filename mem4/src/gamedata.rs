@@ -3,17 +3,25 @@
 //region: extern, use,
 extern crate mem4_common;
 use crate::logmod;
+use crate::websocketcommunication;
 
-use mem4_common::{GameStatus, Player};
+use mem4_common::{
+    build_deck, transition, Card, CardStatusCardFace, GameAction, GameConfig, GameEvent, GameState,
+    GameStatus, Player, WsMessage, PLAYER_COLORS,
+};
 use rand::rngs::SmallRng;
-use rand::seq::SliceRandom;
 use rand::FromEntropy;
-use rand::Rng;
-use strum_macros::AsRefStr;
 use web_sys::WebSocket;
 //endregion
 
 //region: struct, enum
+///key of the name in the local storage
+const STORAGE_NAME: &str = "mem4_name";
+///key of the color in the local storage
+const STORAGE_COLOR: &str = "mem4_color";
+///key of the avatar in the local storage
+const STORAGE_AVATAR: &str = "mem4_avatar";
+
 ///2d size (any UM -pixel, items, percent)
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Size2d {
@@ -22,46 +30,6 @@ pub struct Size2d {
     ///vertical
     pub ver: usize,
 }
-///game config
-#[derive(Serialize, Deserialize, Clone)]
-pub struct GameConfig {
-    ///card moniker - the text/name of the card
-    ///the zero element is card face down or empty, example alphabet begins with index 01 : A
-    pub card_moniker: Vec<String>,
-    ///img filenames
-    pub img_filename: Vec<String>,
-    ///sound filenames
-    pub sound_filename: Vec<String>,
-    ///card image width
-    pub card_width: usize,
-    ///card image height
-    pub card_height: usize,
-    ///number of cards horizontally
-    pub grid_items_hor: usize,
-    ///number of card vertically
-    pub grid_items_ver: usize,
-}
-
-///the 3 possible statuss of one card
-#[derive(Serialize, Deserialize, AsRefStr)]
-pub enum CardStatusCardFace {
-    ///card face down
-    Down,
-    ///card face Up Temporary
-    UpTemporary,
-    ///card face up Permanently
-    UpPermanently,
-}
-///all the data for one card
-#[derive(Serialize, Deserialize)]
-pub struct Card {
-    ///card status
-    pub status: CardStatusCardFace,
-    ///field for src attribute for HTML element imagea and filename of card image
-    pub card_number_and_img_src: usize,
-    ///field for id attribute for HTML element image contains the card index
-    pub card_index_and_id: usize,
-}
 ///game data
 pub struct GameData {
     ///game status: InviteAskBegin,InviteAsking,InviteAsked,Player1,Player2
@@ -76,118 +44,125 @@ pub struct GameData {
     pub ws: WebSocket,
     ///my ws client instance unique id. To not listen the echo to yourself.
     pub my_ws_uid: usize,
+    ///session token issued by the server. Only with it I can reconnect to my ws_uid.
+    pub session_token: String,
     ///players
     pub players: Vec<Player>,
     ///content folder name
     pub content_folder_name: String,
     ///invite asks for a specific game
     pub asked_folder_name: String,
+    ///join code of my room
+    pub room_code: String,
+    ///join code from the join link. The player joins this room as soon as he has his ws_uid.
+    pub join_link_room_code: String,
+    ///ws_uid of the host of my room. He invites the players and starts the game.
+    pub host_ws_uid: usize,
+    ///my room has a game in progress. I can join it late.
+    pub is_game_in_progress: bool,
     ///What player am I
     pub my_player_number: usize,
+    ///my display name from the local storage
+    pub my_name: String,
+    ///my color from the local storage
+    pub my_color: String,
+    ///img src of my avatar from the local storage
+    pub my_avatar: String,
     ///whose turn is now:  player 1,2,3,...
     pub player_turn: usize,
+    ///round of the best-of series, 1 based
+    pub round: usize,
     ///content folders vector
     pub content_folders: Vec<String>,
     ///game_configs
     pub game_config: Option<GameConfig>,
     ///error text
     pub error_text: String,
+    ///info text for the player, not an error
+    pub info_text: String,
+    ///the last ProtocolError from the server. It is shown in the debug panel.
+    pub protocol_error_text: String,
+    ///the last desync of my game state with the checksum from the server. It is shown in the debug panel.
+    pub desync_text: String,
     ///href
     pub href: String,
-    /// is reconnect
+    ///the connection is lost and the client is reconnecting
     pub is_reconnect: bool,
+    ///failed reconnect attempts in a row, for the exponential backoff
+    pub reconnect_attempt: u32,
+    ///msgs sent while the connection is lost. They are sent when the connection is back.
+    pub outbox: Vec<WsMessage>,
+    ///only json msgs, from the url param wire=json. For debugging.
+    pub is_json_only: bool,
+    ///the connection has agreed on the binary msgs in the ResponseWsUid
+    pub is_binary: bool,
+    ///seq of the last game msg applied. The next game msg must have the next seq.
+    pub game_seq: usize,
+    ///game msgs that came after a gap. They wait for the missed msgs.
+    pub pending_game_msgs: Vec<WsMessage>,
+    ///time in milliseconds of the last ResendRequest or StateRequest for the gap
+    pub resend_requested_at: f64,
+    ///msg_id of my last game msg. The server ignores a msg_id it has already seen.
+    pub last_msg_id: usize,
+    ///the join link of the QR code. The QR code is built again only when it changes.
+    pub qr_code_join_link: String,
+    ///the QR code of the join link as svg data url for the img src
+    pub qr_code_src: String,
 }
 //endregion
 
 impl GameData {
     ///prepare new random data
     pub fn prepare_random_data(&mut self) {
-        let item_count_minus_one = unwrap!(unwrap!(self.game_config.as_ref())
-            .card_moniker
-            .len()
-            .checked_sub(1));
-        let players_count = self.players.len();
-        let cards_count = unwrap!(players_count.checked_mul(unwrap!(unwrap!(self
-            .game_config
-            .as_ref())
-        .grid_items_hor
-        .checked_mul(unwrap!(self.game_config.as_ref()).grid_items_ver))));
-        let random_count = unwrap!(cards_count.checked_div(2));
-        //if the number of cards is bigger than the images, i choose all the images.
-        //for the rest I use random.
-        //integer division rounds toward zero
-        let multiple: usize = unwrap!(random_count.checked_div(item_count_minus_one));
-        let rest =
-            unwrap!(random_count.checked_sub(unwrap!(item_count_minus_one.checked_mul(multiple))));
-
+        let mut rng = SmallRng::from_entropy();
+        self.card_grid_data = build_deck(
+            &mut rng,
+            unwrap!(self.game_config.as_ref()),
+            self.players.len(),
+        );
         logmod::log1_str(&format!(
-            "item_count_minus_one {}  players_count {} cards_count {} random_count {} multiple {} rest {}",
-            item_count_minus_one,players_count,cards_count,random_count,multiple,
-            rest,
+            "players_count {} card_grid_data.len {}",
+            self.players.len(),
+            self.card_grid_data.len()
         ));
-
-        //region: find random numbers between 1 and item_count
-        //vec_of_random_numbers is 0 based
-        let mut vec_of_random_numbers = Vec::new();
-        let mut rng = SmallRng::from_entropy();
-        vec_of_random_numbers.clear();
-        for _i in 1..=rest {
-            //how to avoid duplicates
-            let mut num: usize;
-            // a do-while is written as a  loop-break
-            loop {
-                //gen_range is lower inclusive, upper exclusive 26 + 1
-                num = rng.gen_range(1, unwrap!(item_count_minus_one.checked_add(1)));
-                if !vec_of_random_numbers.contains(&num) {
-                    break;
-                }
-            }
-            //push a pair of the same number
-            vec_of_random_numbers.push(num);
-            vec_of_random_numbers.push(num);
+    }
+    ///the game state for the game engine
+    pub fn game_state(&self) -> GameState {
+        GameState {
+            players: self.players.clone(),
+            card_grid_data: self.card_grid_data.clone(),
+            game_status: self.game_status.clone(),
+            card_index_of_first_click: self.card_index_of_first_click,
+            card_index_of_second_click: self.card_index_of_second_click,
+            player_turn: self.player_turn,
+            round: self.round,
         }
-        for _m in 1..=multiple {
-            for i in 1..=item_count_minus_one {
-                vec_of_random_numbers.push(i);
-                vec_of_random_numbers.push(i);
-            }
+    }
+    ///apply the action of my player with the game engine.
+    ///The local state changes immediately, but the server sends back the authoritative state.
+    pub fn apply_action(&mut self, action: &GameAction) {
+        match transition(&self.game_state(), action) {
+            Ok((new_state, _events)) => self.set_game_state(new_state),
+            Err(e) => logmod::log1_str(&format!("action not applied: {}", e)),
         }
-        //endregion
-
-        //region: shuffle the numbers
-        let vrndslice = vec_of_random_numbers.as_mut_slice();
-        vrndslice.shuffle(&mut rng);
-        //endregion
-
-        //region: create Cards from random numbers
-        let mut card_grid_data = Vec::new();
-
-        //Index 0 is special and reserved for FaceDown. Cards start with base 1
-        let new_card = Card {
-            status: CardStatusCardFace::Down,
-            card_number_and_img_src: 0,
-            card_index_and_id: 0,
-        };
-        card_grid_data.push(new_card);
-
-        //create cards and push to the vector
-        for (index, random_number) in vec_of_random_numbers.iter().enumerate() {
-            let new_card = Card {
-                status: CardStatusCardFace::Down,
-                //dereference random number from iterator
-                card_number_and_img_src: *random_number,
-                //card base index will be 1. 0 is reserved for FaceDown.
-                card_index_and_id: unwrap!(index.checked_add(1), "usize overflow"),
-            };
-            card_grid_data.push(new_card);
+    }
+    ///apply the changes of the game state from the server. The whole state comes only
+    ///with GameDataInit and StateSnapshot.
+    pub fn apply_events(&mut self, events: &[GameEvent]) {
+        let mut game_state = self.game_state();
+        for event in events {
+            game_state.apply_event(event);
         }
-        //endregion
-        self.card_grid_data = card_grid_data;
-        logmod::log1_str(&format!(
-            "vec_of_random_numbers.len {} card_grid_data.len {}",
-            vec_of_random_numbers.len(),
-            self.card_grid_data.len()
-        ));
+        self.set_game_state(game_state);
+    }
+    ///the new game state from the game engine
+    fn set_game_state(&mut self, game_state: GameState) {
+        self.players = game_state.players;
+        self.card_grid_data = game_state.card_grid_data;
+        self.game_status = game_state.game_status;
+        self.card_index_of_first_click = game_state.card_index_of_first_click;
+        self.card_index_of_second_click = game_state.card_index_of_second_click;
+        self.player_turn = game_state.player_turn;
     }
     ///asociated function: before Accept, there are not random numbers, just default cards.
     pub fn prepare_for_empty() -> Vec<Card> {
@@ -207,10 +182,15 @@ impl GameData {
     ///constructor of game data
     pub fn new(ws: WebSocket, my_ws_uid: usize) -> Self {
         let mut players = Vec::new();
-        players.push(Player {
-            ws_uid: 0,
-            points: 0,
-        });
+        players.push(Player::new(0));
+        //without the local storage the profile has the default values
+        let storage = crate::local_storage();
+        let stored = |key: &str| {
+            storage
+                .as_ref()
+                .and_then(|storage| storage.get_item(key).ok())
+                .and_then(|value| value)
+        };
         //return from constructor
         GameData {
             card_grid_data: Self::prepare_for_empty(),
@@ -218,12 +198,22 @@ impl GameData {
             card_index_of_second_click: 0,
             ws,
             my_ws_uid,
+            session_token: "".to_string(),
             players,
             game_status: GameStatus::InviteAskBegin,
             content_folder_name: "alphabet".to_string(),
             asked_folder_name: "".to_string(),
+            room_code: "".to_string(),
+            join_link_room_code: "".to_string(),
+            host_ws_uid: 0,
+            is_game_in_progress: false,
             my_player_number: 1,
+            my_name: stored(STORAGE_NAME).unwrap_or_default(),
+            my_color: stored(STORAGE_COLOR)
+                .unwrap_or_else(|| unwrap!(PLAYER_COLORS.first()).to_string()),
+            my_avatar: stored(STORAGE_AVATAR).unwrap_or_default(),
             player_turn: 0,
+            round: 0,
             content_folders: vec![
                 String::from("alphabet"),
                 String::from("animals"),
@@ -232,10 +222,105 @@ impl GameData {
             ],
             game_config: None,
             error_text: "".to_string(),
+            info_text: "".to_string(),
+            protocol_error_text: "".to_string(),
+            desync_text: "".to_string(),
             href: "".to_string(),
             is_reconnect: false,
+            reconnect_attempt: 0,
+            outbox: Vec::new(),
+            is_json_only: false,
+            is_binary: false,
+            game_seq: 0,
+            pending_game_msgs: Vec::new(),
+            resend_requested_at: 0.0,
+            last_msg_id: 0,
+            qr_code_join_link: "".to_string(),
+            qr_code_src: "".to_string(),
+        }
+    }
+    ///my player with my name, color and avatar
+    pub fn my_player(&self) -> Player {
+        let mut player = Player::new(self.my_ws_uid);
+        player.name = self.my_name.clone();
+        player.color = self.my_color.clone();
+        player.avatar = self.my_avatar.clone();
+        player
+    }
+    ///save my profile in the local storage and update my player in the list of players
+    pub fn save_my_profile(&mut self) {
+        match crate::local_storage() {
+            Some(storage) => {
+                for (key, value) in &[
+                    (STORAGE_NAME, &self.my_name),
+                    (STORAGE_COLOR, &self.my_color),
+                    (STORAGE_AVATAR, &self.my_avatar),
+                ] {
+                    if storage.set_item(key, value).is_err() {
+                        logmod::log1_str("local storage not available");
+                    }
+                }
+            }
+            None => logmod::log1_str("local storage not available"),
+        }
+        let my_player = self.my_player();
+        if let Some(player) = self
+            .players
+            .iter_mut()
+            .find(|p| p.ws_uid == my_player.ws_uid)
+        {
+            player.set_profile(&my_player);
+        }
+    }
+    ///the name of the player for the UI
+    pub fn player_name(&self, player_number: usize) -> String {
+        match player_number
+            .checked_sub(1)
+            .and_then(|index| self.players.get(index))
+        {
+            Some(player) => player.display_name(player_number),
+            None => format!("Player{}", player_number),
+        }
+    }
+    ///the color of the player for the UI, empty if unknown
+    pub fn player_color(&self, player_number: usize) -> String {
+        player_number
+            .checked_sub(1)
+            .and_then(|index| self.players.get(index))
+            .map_or_else(String::new, |player| player.color.clone())
+    }
+    ///send the msg now or keep it in the outbox while the connection is lost.
+    ///The order of the msgs is kept.
+    pub fn send_msg(&mut self, ws_message: WsMessage) {
+        if self.is_reconnect
+            || !self.outbox.is_empty()
+            || !websocketcommunication::ws_send_msg(&self.ws, &ws_message, self.is_binary)
+        {
+            logmod::log1_str("msg in the outbox");
+            self.outbox.push(ws_message);
+        }
+    }
+    ///a new msg_id for my next game msg
+    pub fn next_msg_id(&mut self) -> usize {
+        self.last_msg_id = unwrap!(self.last_msg_id.checked_add(1));
+        self.last_msg_id
+    }
+    ///a new game starts with seq 0
+    pub fn reset_game_seq(&mut self) {
+        self.game_seq = 0;
+        self.pending_game_msgs.clear();
+    }
+    ///send the msgs from the outbox after the server has answered the Hello of the new connection
+    pub fn flush_outbox(&mut self) {
+        let outbox = std::mem::replace(&mut self.outbox, Vec::new());
+        for ws_message in outbox {
+            self.send_msg(ws_message);
         }
     }
+    ///am I the host of my room
+    pub fn is_host(&self) -> bool {
+        self.host_ws_uid != 0 && self.host_ws_uid == self.my_ws_uid
+    }
     ///check only if status InviteAskBegin
     pub fn is_status_invite_ask_begin(&self) -> bool {
         #[allow(clippy::wildcard_enum_match_arm)]
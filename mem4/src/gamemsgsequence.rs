@@ -0,0 +1,170 @@
+//! gamemsgsequence.rs - the game msgs are applied strictly in the order of their seq.
+//! The server numbers the game msgs of every game. A msg with an already applied seq is a duplicate.
+//! After a gap the next msgs wait and the client asks the server to resend the missed msgs.
+//! If the gap is still open after too many waiting msgs or after the resend timeout, the client requests the StateSnapshot.
+//! After every game msg the client compares the checksum of its state. On a desync it requests the StateSnapshot.
+
+//region: use
+use crate::rootrenderingcomponent::RootRenderingComponent;
+use crate::statusplaybefore1stcard;
+use crate::statusplaybefore2ndcard;
+use crate::statustaketurnbegin;
+use crate::logmod;
+
+use mem4_common::WsMessage;
+//endregion
+
+//region: const
+///with so many msgs waiting after a gap the client stops waiting for the resend
+const MAX_PENDING_GAME_MSGS: usize = 8;
+///milliseconds to wait for the resend of the missed msgs
+const RESEND_TIMEOUT_MS: f64 = 5000.0;
+//endregion
+
+///a game msg or the StateSnapshot from the server. The duplicates are ignored.
+///After a gap the msg waits in pending_game_msgs and the client sends ResendRequest.
+pub fn on_msg_game(rrc: &mut RootRenderingComponent, ws_message: WsMessage) {
+    let seq = ws_message.seq().unwrap_or(0);
+    let next_seq = unwrap!(rrc.game_data.game_seq.checked_add(1));
+    if let WsMessage::StateSnapshot { .. } = ws_message {
+        //the snapshot has the whole state until its seq
+        rrc.game_data.game_seq = seq;
+        apply_game_msg(rrc, ws_message);
+    } else if seq < next_seq {
+        logmod::log1_str(&format!("duplicate game msg seq {}", seq));
+        return;
+    } else if seq == next_seq {
+        rrc.game_data.game_seq = seq;
+        apply_game_msg(rrc, ws_message);
+    } else {
+        if rrc
+            .game_data
+            .pending_game_msgs
+            .iter()
+            .all(|pending| pending.seq() != Some(seq))
+        {
+            //only the first msg after the gap asks for the resend
+            let is_new_gap = rrc.game_data.pending_game_msgs.is_empty();
+            rrc.game_data.pending_game_msgs.push(ws_message);
+            if is_new_gap {
+                logmod::log1_str(&format!("gap in game msgs, resend from seq {}", next_seq));
+                rrc.game_data.resend_requested_at = js_sys::Date::now();
+                rrc.game_data.send_msg(WsMessage::ResendRequest {
+                    my_ws_uid: rrc.game_data.my_ws_uid,
+                    from_seq: next_seq,
+                });
+            } else {
+                check_pending_game_msgs(rrc);
+            }
+        }
+        return;
+    }
+    apply_pending_game_msgs(rrc);
+}
+
+///the gap is still open: the resend has not come. With too many waiting msgs or after the
+///resend timeout the client requests the StateSnapshot and repeats it after every timeout.
+///It is checked on every game msg and on every Ping of the server.
+pub fn check_pending_game_msgs(rrc: &mut RootRenderingComponent) {
+    if rrc.game_data.pending_game_msgs.is_empty() {
+        return;
+    }
+    let is_timeout = js_sys::Date::now() - rrc.game_data.resend_requested_at >= RESEND_TIMEOUT_MS;
+    if is_timeout || rrc.game_data.pending_game_msgs.len() == MAX_PENDING_GAME_MSGS {
+        logmod::log1_str(&format!(
+            "gap in game msgs after seq {} not resent, request state",
+            rrc.game_data.game_seq
+        ));
+        rrc.game_data.resend_requested_at = js_sys::Date::now();
+        rrc.game_data.send_msg(WsMessage::StateRequest {
+            my_ws_uid: rrc.game_data.my_ws_uid,
+        });
+    }
+}
+
+///the waiting msgs that now continue the seq are applied. The older ones are dropped.
+fn apply_pending_game_msgs(rrc: &mut RootRenderingComponent) {
+    let game_seq = rrc.game_data.game_seq;
+    rrc.game_data
+        .pending_game_msgs
+        .retain(|pending| pending.seq().map_or(false, |seq| seq > game_seq));
+    loop {
+        let next_seq = unwrap!(rrc.game_data.game_seq.checked_add(1));
+        match rrc
+            .game_data
+            .pending_game_msgs
+            .iter()
+            .position(|pending| pending.seq() == Some(next_seq))
+        {
+            Some(index) => {
+                let ws_message = rrc.game_data.pending_game_msgs.remove(index);
+                rrc.game_data.game_seq = next_seq;
+                apply_game_msg(rrc, ws_message);
+            }
+            None => break,
+        }
+    }
+}
+
+///the game msg in its turn goes to the function of its status.
+///The game msgs bring only the changes of the state, the StateSnapshot the whole state.
+fn apply_game_msg(rrc: &mut RootRenderingComponent, ws_message: WsMessage) {
+    let checksum = ws_message.checksum();
+    #[allow(clippy::wildcard_enum_match_arm)]
+    match ws_message {
+        WsMessage::PlayerClick1stCard { events, .. } => {
+            statusplaybefore1stcard::on_msg_player_click_1st_card(rrc, &events)
+        }
+        WsMessage::PlayerClick2ndCard { events, .. } => {
+            statusplaybefore2ndcard::on_msg_player_click_2nd_card(rrc, &events)
+        }
+        WsMessage::TakeTurnBegin { events, .. } => {
+            statustaketurnbegin::on_msg_take_turn_begin(rrc, &events)
+        }
+        WsMessage::TakeTurnEnd { events, .. } => {
+            statustaketurnbegin::on_msg_take_turn_end(rrc, &events)
+        }
+        WsMessage::GameOverPlayAgainBegin { events, .. } => {
+            statusplaybefore2ndcard::on_msg_play_again(rrc, &events)
+        }
+        WsMessage::StateSnapshot {
+            players,
+            card_grid_data,
+            game_status,
+            card_index_of_first_click,
+            card_index_of_second_click,
+            player_turn,
+            round,
+            ..
+        } => rrc.on_msg_state_snapshot(
+            players,
+            game_status,
+            card_grid_data,
+            card_index_of_first_click,
+            card_index_of_second_click,
+            player_turn,
+            round,
+        ),
+        _ => (),
+    }
+    //a msg without checksum cannot be checked
+    if let Some(checksum) = checksum.filter(|&checksum| checksum != 0) {
+        check_desync(rrc, checksum);
+    }
+}
+
+///my game state must have the same checksum as the state of the server.
+///A desync is shown in the debug panel and the client requests the authoritative state.
+fn check_desync(rrc: &mut RootRenderingComponent, checksum: u64) {
+    let my_checksum = rrc.game_data.game_state().checksum();
+    if my_checksum != checksum {
+        rrc.game_data.desync_text = format!(
+            "seq {} my checksum {:x} server checksum {:x}",
+            rrc.game_data.game_seq, my_checksum, checksum
+        );
+        logmod::log1_str(&format!("desync: {}", rrc.game_data.desync_text));
+        rrc.game_data.send_msg(WsMessage::StateRequest {
+            my_ws_uid: rrc.game_data.my_ws_uid,
+        });
+    }
+}
@@ -51,6 +51,7 @@
 //! If no data has changed, the cached subcomponent Node is used. This is more efficient and performant.  
 //! ##GameData
 //! All the game data are in this simple struct.  
+//! The rules of the game are in the game engine in mem4_common. The same rules are used on the server.  
 //! ## WebSocket communication
 //! HTML5 has finally bring a true stateful bidirectional communication.  
 //! Most of the programming problems are more easily and effectively solved this way.  
@@ -61,10 +62,53 @@
 //! I send simple structs text messages in json format between the players.  
 //! They are all in the WsMsg enum and therefore interchangeable.  
 //! The WebSocket server is coded especially for this game and recognizes 3 types of msg:
-//! - msg to broadcast to every other player
-//! - msg to send only to the actual game players
+//! - msg to manage the rooms: the host creates a room and the other players join it with the join code
+//! - msg to send only to the other members of the room
+//! - player actions that the server applies to the authoritative game state and then sends the new state to all players  
+//! If a msg cannot be parsed, validated or routed, the server sends back a ProtocolError with a code, a reason and the msg type.  
+//! The client shows it in the debug panel.  
+//! The first msg is the handshake Hello with the protocol version and the capabilities of the client.  
+//! A client too old for the server gets HelloRejected and shows the prompt to reload the page.  
 //! ## WS reconnect
-//! TODO: It looks that plain web sockets have often connection problems and they disconnect here and there. Creating a good reconnect is pretty challenging.  
+//! Plain web sockets have often connection problems and they disconnect here and there.  
+//! The client reconnects automatically with exponential backoff: 1, 2, 4,... seconds, at most 30 seconds between the attempts.  
+//! The msgs sent while the connection is lost wait in the outbox and are sent after the server answers the new Hello.  
+//! The game stays usable and shows only a small "reconnecting…" indicator.  
+//! The server issues the ws_uid and a secret session token in the msg ResponseWsUid.  
+//! On reconnect the client sends the session token as url_param and gets back the same ws_uid.  
+//! Without a valid token nobody can take over the ws_uid of another player.  
+//! After the reconnect the server sends the StateSnapshot of the game and the client overwrites its GameData.  
+//! Every game msg has the seq from the server and the msg_id from its sender. The client applies the game msgs  
+//! in the order of the seq and ignores the duplicates. After a gap it asks for the missed msgs with ResendRequest.  
+//! If they do not come in time or too many msgs wait after the gap, it asks for the StateSnapshot with StateRequest.  
+//! Every game msg has the checksum of the game state. A client with a different state shows the desync  
+//! in the debug panel and gets the authoritative StateSnapshot from the server with StateRequest.  
+//! The game msgs bring only the changes of the state as events: a card has a new face, a player has new points...  
+//! The whole card grid is sent only with GameDataInit and with the StateSnapshot.  
+//! The client offers the capability bincode in the Hello. If the server agrees, the msgs of this connection are  
+//! bincode in binary frames, much smaller than json. With the url param `?wire=json` the msgs stay json for debugging.  
+//! The server pings the client and the client answers with Pong. A dead connection is removed.  
+//! The other players get PlayerLeft and the scores show the player as absent. The turns skip him.  
+//! If the absent player is in turn, the server skips his turn after a grace period. On reconnect they get PlayerRejoined.  
+//! The host invites the players and starts the game. If the host leaves, the server passes the host role  
+//! to the next connected player with HostChanged. The new host gets the lobby and the "Start Game?".  
+//! A player that joins the room late can join the game in progress. He takes over the cards of an absent player  
+//! or gets his own slice of newly dealt cards and the last place in the turns.  
+//! The absent player gets SeatTaken on reconnect and can join the game in progress again.  
+//! After the game over the host invites the same room again for a rematch, with the same or a different content.  
+//! The rounds make a best-of series. The scores show the totals and the starting player rotates.  
+//! The scoreboard shows all the players, highlights the player in turn and marks the absent players.  
+//! At the game over the summary shows the ranking, the winner or the winners on a tie and for every player  
+//! the pairs found, the mismatches, the longest streak and the play time. The server measures the time.  
+//! Every player chooses a name, a color and an avatar from the card images of the content before the invite or the accept.  
+//! The profile is saved in the local storage. The names are in the turn prompts, the scoreboard and the lobby.  
+//! The grid background of every phone is tinted with the color of its player.  
+//! The lobby of the host lists the accepted players by name. The host reorders them as they sit around the table  
+//! or removes a phone that accepted by mistake. This order goes into GameDataInit and is the order of the turns.  
+//! A player can decline the invite and the host can cancel it. The server cancels the invite after a timeout.  
+//! The players go back to InviteAskBegin with a message.  
+//! The host screen shows a QR code with the join link of the room, for example `?room=ABCD&pack=animals`.  
+//! The phone that opens the join link joins the room as soon as it has its ws_uid and gets the pending invite on the accept screen.  
 //! ## The game flow
 //! In a few words: Status1 - User action - Status2, Status1 - WsMessage - Status2
 //! In one moment the game is in a certain Game Status. The user then makes an action.
@@ -74,15 +118,18 @@
 //!  
 //! | Game Status1       | Render                     | User action                                 | Condition                            | GameStatus2 t.p.   | Sends Msg          | On rcv Msg o.p.              | GameStatus2 o.p.                   |
 //! | ------------------ | -------------------------- | ------------------------------------------- | ------------------------------------ | ----------------   | ----------------   | --------------------------   | --------------------------------   |
+//! | InviteAskBegin     | div_invite_ask_begin       | div_join_room_on_click                      | -                                    | -                  | RoomJoin           | on_msg_room_joined           | -                                  |
 //! | InviteAskBegin     | div_invite_ask_begin       | div_invite_ask_begin_on_click               | -                                    | InviteAsking       | Invite             | on_msg_invite                | InviteAsked                        |
 //! | InviteAsked        | div_invite_asked, div_play_accepted | div_invite_asked_on_click          | -                                    | PlayAccepted       | PlayAccept         | on_msg_play_accept           | -                                  |
+//! | InviteAsked        | div_invite_asked           | div_invite_decline_on_click                 | -                                    | InviteAskBegin     | InviteDecline      | on_msg_invite_decline        | -                                  |
 //! | InviteAsking       | div_invite_asking          | game_data_init                              | -                                    | PlayBefore1stCard  | GameDataInit       | on_msg_game_data_init        | PlayBefore1stCard                  |
+//! | InviteAsking       | div_invite_asking          | div_invite_cancel_on_click                  | also the server on invite timeout    | InviteAskBegin     | InviteCancel       | on_msg_invite_cancel         | InviteAskBegin                     |
 //! | PlayBefore1stCard  | div_grid_container         | div_grid_item_on_click, on_click_1st_card();| -                                    | PlayBefore2ndCard  | PlayerClick1stCard | on_msg_player_click_1st_card | PlayBefore2ndCard                  |
 //! | PlayBefore2ndCard  | div_grid_container         | div_grid_item_on_click, on_click_2nd_card();| If card match and points<all point   | PlayBefore1stCard  | PlayerClick2ndCard | on_msg_player_click_2nd_card | PlayBefore1stCard                  |
 //! | -II-               | -II-                       | -II-                                        | If card match and points=>all points | GameOverPlayAgainBegin | GameOverPlayAgainBegin  | on_msg_play_again   | GameOverPlayAgainBegin             |
 //! | -II-               | -II-                       | -II-                                        | else                                 | TakeTurnBegin      | TakeTurnBegin      | on_msg_take_turn             | TakeTurnBegin                      |
 //! | TakeTurnBegin      | div_take_turn_begin        | div_take_turn_begin_on_click                | -                                    | PlayBefore1stCard  | TakeTurnEnd        | on_msg_take_turn_end         | PlayBefore1stCard, the next player |
-//! | GameOverPlayAgainBegin | div_play_again         | window.location().reload()                  | -                                    | -                  | -                  | -                            | -                                  |
+//! | GameOverPlayAgainBegin | div_play_again         | div_invite_ask_begin_on_click               | only the host                        | InviteAsking       | Invite             | on_msg_invite                | InviteAsked                        |
 //! |  |  |  |  |  |  |  |  |
 //!  
 //! t.p. = this player,   o.p. = other players,  rrc = root_rendering_component, rcv = receive
@@ -196,11 +243,14 @@ mod divcardmoniker;
 mod divfordebugging;
 mod divgridcontainer;
 mod divplayeractions;
+mod divplayerprofile;
+mod divqrcode;
 mod divplayersandscores;
 mod divrulesanddescription;
 mod fetchmod;
 mod fetchgameconfig;
 mod gamedata;
+mod gamemsgsequence;
 mod javascriptimportmod;
 mod logmod;
 mod rootrenderingcomponent;
@@ -230,10 +280,8 @@ extern crate web_sys;
 #[macro_use]
 extern crate unwrap;
 extern crate conv;
+extern crate qrcode;
 
-use rand::rngs::SmallRng;
-use rand::FromEntropy;
-use rand::Rng;
 use wasm_bindgen::prelude::*;
 //endregion
 
@@ -252,16 +300,27 @@ pub fn run() -> Result<(), JsValue> {
         "No #div_for_virtual_dom"
     );
 
-    let mut rng = SmallRng::from_entropy();
-    //my_ws_uid is random generated on the client and sent to
-    //WebSocket server with an url param
-    let my_ws_uid: usize = rng.gen_range(1, 9999);
+    //my_ws_uid is issued by the WebSocket server in the msg ResponseWsUid.
+    //Until then it is 0.
+    let my_ws_uid: usize = 0;
 
     //find out URL
     let location_href = unwrap!(window.location().href(), "href not known");
+    //the join link from the QR code has the query ?room=ABCD&pack=animals
+    //The WebSocket and the fetch need the URL without the query.
+    let (location_href, query) = match location_href.find(|c| c == '?' || c == '#') {
+        Some(pos) => (
+            location_href.get(..pos).unwrap_or("").to_string(),
+            location_href.get(pos..).unwrap_or("").to_string(),
+        ),
+        None => (location_href, "".to_string()),
+    };
 
+    //the url param wire=json keeps the msgs in json for debugging
+    let is_json_only = query_param(&query, "wire").map_or(false, |wire| wire == "json");
     //WebSocket connection
-    let ws = websocketcommunication::setup_ws_connection(location_href.clone(), my_ws_uid);
+    let ws =
+        websocketcommunication::setup_ws_connection(location_href.clone(), "", is_json_only);
     //I don't know why is needed to clone the WebSocket connection
     let ws_c = ws.clone();
 
@@ -271,6 +330,16 @@ pub fn run() -> Result<(), JsValue> {
     let mut root_rendering_component =
         rootrenderingcomponent::RootRenderingComponent::new(ws_c, my_ws_uid);
     root_rendering_component.game_data.href = location_href;
+    root_rendering_component.game_data.is_json_only = is_json_only;
+    //with the join link the player joins the room as soon as he has his ws_uid
+    if let Some(room_code) = query_param(&query, "room") {
+        root_rendering_component.game_data.join_link_room_code = room_code.to_uppercase();
+    }
+    if let Some(pack) = query_param(&query, "pack") {
+        if root_rendering_component.game_data.content_folders.contains(&pack) {
+            root_rendering_component.game_data.asked_folder_name = pack;
+        }
+    }
 
     // Mount the component to the `<div id="div_for_virtual_dom">`.
     let vdom = dodrio::Vdom::new(&div_for_virtual_dom, root_rendering_component);
@@ -284,10 +353,32 @@ pub fn run() -> Result<(), JsValue> {
 }
 //endregion
 
+///the value of the parameter in the query ?room=ABCD&pack=animals
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query
+        .trim_start_matches(|c| c == '?' || c == '#')
+        .split('&')
+        .filter_map(|pair| {
+            let mut key_value = pair.splitn(2, '=');
+            match (key_value.next(), key_value.next()) {
+                (Some(key), Some(value)) if key == name && value != "" => Some(value.to_string()),
+                _ => None,
+            }
+        })
+        .next()
+}
+
 /// Get the top-level window's session storage.
 /// TODO: to save user preferences maybe?
 pub fn session_storage() -> web_sys::Storage {
     let window = unwrap!(web_sys::window(), "error: web_sys::window");
     window.session_storage().unwrap_throw().unwrap_throw()
 }
+
+/// Get the top-level window's local storage. The player profile stays there between visits.
+/// None if the browser blocks the storage, for example in private mode.
+pub fn local_storage() -> Option<web_sys::Storage> {
+    let window = unwrap!(web_sys::window(), "error: web_sys::window");
+    window.local_storage().ok().and_then(|storage| storage)
+}
 //endregion
@@ -6,14 +6,20 @@ use crate::divfordebugging;
 use crate::divgridcontainer;
 use crate::divplayeractions;
 use crate::divplayersandscores;
+use crate::divqrcode;
 use crate::divrulesanddescription;
+use crate::fetchgameconfig;
 use crate::gamedata::GameData;
-//use crate::logmod;
+use crate::logmod;
+use crate::websocketreconnect;
 
 use dodrio::builder::text;
 use dodrio::bumpalo::{self, Bump};
 use dodrio::{Cached, Node, Render};
-use mem4_common::GameStatus;
+use mem4_common::{
+    Card, GameConfig, GameStatus, Player, ProtocolErrorCode, WsMessage, CAPABILITY_BINARY,
+    PROTOCOL_VERSION,
+};
 use typed_html::dodrio;
 use web_sys::WebSocket;
 use conv::{ConvAsUtil};
@@ -53,6 +59,7 @@ impl RootRenderingComponent {
     }
     ///check invalidate render cache for all sub components
     pub fn check_invalidate_for_all_components(&mut self) {
+        divqrcode::update_qr_code(&mut self.game_data);
         if self
             .cached_players_and_scores
             .update_intern_cache(&self.game_data)
@@ -67,6 +74,9 @@ impl RootRenderingComponent {
         self.game_data.prepare_random_data();
         self.game_data.game_status = GameStatus::PlayBefore1stCard;
         self.game_data.player_turn = 1;
+        //the server sends the round and the starting player of the series in the StateSnapshot
+        self.game_data.round = 1;
+        self.game_data.reset_game_seq();
     }
     ///reset the data to replay the game
     pub fn reset(&mut self) {
@@ -86,38 +96,176 @@ impl RootRenderingComponent {
     //region: all functions for receive message (like events)
     // I separate the code into functions to avoid looking at all that boilerplate in the big match around futures and components.
     // All the data changing must be encapsulated inside these functions.
-    ///msg response with uid, just to check. because the WebSocket server
-    ///gets the uid from the client in the url_param. The client generates a random number.
-    pub fn on_response_ws_uid(&mut self, your_ws_uid: usize) {
-        if self.game_data.my_ws_uid != your_ws_uid {
-            self.game_data.error_text = "my_ws_uid is incorrect!".to_string();
+    ///msg response with the ws_uid and session token issued by the server.
+    ///After a reconnect with an expired session the server issues a new ws_uid.
+    ///The connection is back and the msgs from the outbox are sent, in binary if the server has agreed.
+    pub fn on_response_ws_uid(
+        &mut self,
+        your_ws_uid: usize,
+        session_token: &str,
+        capabilities: &[String],
+    ) {
+        if self.game_data.my_ws_uid != 0 && self.game_data.my_ws_uid != your_ws_uid {
+            self.game_data.error_text =
+                "The session has expired. Please reload the page.".to_string();
+            //the msgs of the old ws_uid are not valid anymore
+            self.game_data.outbox.clear();
+        }
+        self.game_data.my_ws_uid = your_ws_uid;
+        self.game_data.session_token = session_token.to_string();
+        self.game_data.is_reconnect = false;
+        self.game_data.reconnect_attempt = 0;
+        self.game_data.is_binary = capabilities.iter().any(|c| c == CAPABILITY_BINARY);
+        self.game_data.flush_outbox();
+        //the join link goes straight to the room. The pending invite brings the accept screen.
+        if self.game_data.join_link_room_code != "" {
+            let room_code = self.game_data.join_link_room_code.clone();
+            self.game_data.join_link_room_code = "".to_string();
+            self.game_data.send_msg(WsMessage::RoomJoin {
+                my_ws_uid: your_ws_uid,
+                room_code,
+            });
+        }
+        self.check_invalidate_for_all_components();
+    }
+
+    ///msg hello rejected: this client is too old for the server. The user must reload the page.
+    pub fn on_msg_hello_rejected(&mut self, protocol_version: usize, reason: &str) {
+        self.game_data.error_text = format!(
+            "This version of the game ({}) is too old for the server ({}). {} Please reload the page.",
+            PROTOCOL_VERSION, protocol_version, reason
+        );
+    }
+
+    ///msg protocol error: the server has rejected my msg. It is shown in the debug panel.
+    pub fn on_msg_protocol_error(
+        &mut self,
+        code: &ProtocolErrorCode,
+        reason: &str,
+        msg_type: &str,
+    ) {
+        self.game_data.protocol_error_text = format!("{} {}: {}", msg_type, code, reason);
+        logmod::log1_str(&format!(
+            "ProtocolError: {}",
+            self.game_data.protocol_error_text
+        ));
+    }
+
+    ///msg player left: the player has lost the connection. The turns skip him.
+    pub fn on_msg_player_left(&mut self, ws_uid: usize) {
+        self.game_data.info_text = match self.set_player_absent(ws_uid, true) {
+            Some(player_number) => format!(
+                "{} lost the connection. The turns skip him.",
+                self.game_data.player_name(player_number)
+            ),
+            None => "A player lost the connection.".to_string(),
+        };
+        self.check_invalidate_for_all_components();
+    }
+
+    ///msg player rejoined: the absent player is back
+    pub fn on_msg_player_rejoined(&mut self, ws_uid: usize) {
+        self.game_data.info_text = match self.set_player_absent(ws_uid, false) {
+            Some(player_number) => {
+                format!("{} is back.", self.game_data.player_name(player_number))
+            }
+            None => "".to_string(),
+        };
+        self.check_invalidate_for_all_components();
+    }
+
+    ///msg host changed: the host has left. The new host gets the lobby and can start the game.
+    pub fn on_msg_host_changed(
+        &mut self,
+        host_ws_uid: usize,
+        players: Vec<Player>,
+        vdom_weak: dodrio::VdomWeak,
+    ) {
+        let was_host = self.game_data.is_host();
+        self.game_data.host_ws_uid = host_ws_uid;
+        #[allow(clippy::wildcard_enum_match_arm)]
+        let is_in_lobby = match self.game_data.game_status {
+            GameStatus::InviteAsking | GameStatus::InviteAsked | GameStatus::PlayAccepted => true,
+            _ => false,
+        };
+        if self.game_data.is_host() {
+            if !was_host {
+                self.game_data.info_text = "You are the new host.".to_string();
+            }
+            if is_in_lobby && !was_host {
+                //I take over the lobby and the "Start Game?" of the old host
+                self.game_data.players = players;
+                self.game_data.my_player_number = 1;
+                self.game_data.game_status = GameStatus::InviteAsking;
+                fetchgameconfig::fetch_game_config_request(self, vdom_weak);
+            }
+        } else if was_host {
+            self.game_data.info_text = "The host role has passed to another player.".to_string();
+            if is_in_lobby {
+                //now I wait in the lobby of the new host
+                self.game_data.game_status = GameStatus::PlayAccepted;
+            }
         }
+        self.check_invalidate_for_all_components();
+    }
+
+    ///mark the player as absent or present. Returns the player number.
+    fn set_player_absent(&mut self, ws_uid: usize, is_absent: bool) -> Option<usize> {
+        let index = self
+            .game_data
+            .players
+            .iter()
+            .position(|p| p.ws_uid == ws_uid)?;
+        unwrap!(self.game_data.players.get_mut(index)).is_absent = is_absent;
+        index.checked_add(1)
+    }
+
+    ///msg state snapshot after reconnect or late join. The state from the server overwrites the local state.
+    pub fn on_msg_state_snapshot(
+        &mut self,
+        players: Vec<Player>,
+        game_status: GameStatus,
+        card_grid_data: Vec<Card>,
+        card_index_of_first_click: usize,
+        card_index_of_second_click: usize,
+        player_turn: usize,
+        round: usize,
+    ) {
+        self.game_data.players = players;
+        self.game_data.card_grid_data = card_grid_data;
+        self.game_data.game_status = game_status;
+        self.game_data.card_index_of_first_click = card_index_of_first_click;
+        self.game_data.card_index_of_second_click = card_index_of_second_click;
+        self.game_data.player_turn = player_turn;
+        self.game_data.round = round;
+        //find my player number
+        let my_ws_uid = self.game_data.my_ws_uid;
+        if let Some(index) = self
+            .game_data
+            .players
+            .iter()
+            .position(|p| p.ws_uid == my_ws_uid)
+        {
+            self.game_data.my_player_number = unwrap!(index.checked_add(1));
+        }
+        self.check_invalidate_for_all_components();
     }
 
     ///on game data init
     pub fn on_msg_game_data_init(
         &mut self,
-        card_grid_data: &str,
-        game_config: &str,
-        players: &str,
+        card_grid_data: Vec<Card>,
+        game_config: GameConfig,
+        players: Vec<Player>,
     ) {
         self.game_data.content_folder_name = self.game_data.asked_folder_name.clone();
         self.game_data.game_status = GameStatus::PlayBefore1stCard;
         self.game_data.player_turn = 1;
-        self.game_data.card_grid_data = unwrap!(
-            serde_json::from_str(card_grid_data),
-            "error serde_json::from_str(card_grid_data)"
-        );
-
-        self.game_data.game_config = unwrap!(
-            serde_json::from_str(game_config),
-            "error serde_json::from_str(game_config)"
-        );
-
-        self.game_data.players = unwrap!(
-            serde_json::from_str(players),
-            "error serde_json::from_str(players)"
-        );
+        self.game_data.round = 1;
+        self.game_data.card_grid_data = card_grid_data;
+        self.game_data.game_config = Some(game_config);
+        self.game_data.players = players;
+        self.game_data.reset_game_seq();
 
         //find my player number
         for index in 0..self.game_data.players.len() {
@@ -174,6 +322,8 @@ impl Render for RootRenderingComponent {
                 {vec![divcardmoniker::div_grid_card_moniker(self, bump)]}
                 {vec![divgridcontainer::div_grid_container(self,bump,&xmax_grid_size)]}
                 {vec![divplayeractions::div_player_actions_from_game_status(self, bump)]}
+                {divplayeractions::div_info_text(self, bump)}
+                {websocketreconnect::div_reconnecting(self, bump)}
                 {vec![self.cached_players_and_scores.render(bump)]}
                 {vec![divfordebugging::div_for_debugging(self, bump)]}
                 {vec![self.cached_rules_and_description.render(bump)]}
@@ -190,6 +340,15 @@ impl Render for RootRenderingComponent {
                                 .into_bump_str(),
                             )]}
                     </h1>
+                    <div class="div_clickable" onclick={move |_root, _vdom, _event| {
+                        //the reload gets the new wasm from the server
+                        let window = unwrap!(web_sys::window(), "error: web_sys::window");
+                        unwrap!(window.location().reload(), "error: location.reload");
+                    }}>
+                        <h2 style= "color:green;">
+                            {vec![text(bumpalo::format!(in bump, "Reload{}", "").into_bump_str())]}
+                        </h2>
+                    </div>
                 </div>
             )
         }
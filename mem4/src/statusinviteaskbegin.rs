@@ -2,15 +2,17 @@
 
 //region: use
 use crate::rootrenderingcomponent::RootRenderingComponent;
-use crate::websocketcommunication;
 use crate::logmod;
 use crate::fetchgameconfig;
+use crate::divplayerprofile;
+use crate::divqrcode;
 
 use dodrio::builder::text;
 use dodrio::bumpalo::{self, Bump};
 use dodrio::Node;
-use mem4_common::{GameStatus, Player, WsMessage};
+use mem4_common::{GameConfig, GameStatus, Player, WsMessage};
 use typed_html::dodrio;
+use wasm_bindgen::JsCast;
 //endregion
 
 ///render invite ask begin, ask to play for multiple contents/folders
@@ -23,6 +25,64 @@ where
 {
     logmod::log1_str("GameStatus::InviteAskBegin");
     let mut vec_of_nodes = Vec::new();
+    //join the room of another player with the join code
+    vec_of_nodes.push(dodrio!(bump,
+    <div>
+        <input id="input_room_code" type="text" style="font-size:24px;width:100px;text-transform:uppercase;">
+        </input>
+        <div class="div_clickable" onclick={move |root, vdom, _event| {
+                let rrc = root.unwrap_mut::<RootRenderingComponent>();
+                div_join_room_on_click(rrc);
+                vdom.schedule_render();
+                }}>
+            <h2 id= "ws_elem" style= "color:green;">
+                {vec![text(bumpalo::format!(in bump, "Join room!{}", "").into_bump_str())]}
+            </h2>
+        </div>
+    </div>
+    ));
+    //my name and color for the other players
+    vec_of_nodes.push(divplayerprofile::div_player_profile(
+        root_rendering_component,
+        bump,
+    ));
+    if root_rendering_component.game_data.room_code != "" {
+        let room_text = if root_rendering_component.game_data.is_host() {
+            "you are the host, choose a game"
+        } else {
+            "wait for the invite"
+        };
+        vec_of_nodes.push(dodrio!(bump,
+        <h2 style= "color:red;">
+            {vec![text(bumpalo::format!(in bump, "Room {}: {}.",
+            root_rendering_component.game_data.room_code, room_text).into_bump_str())]}
+        </h2>
+        ));
+        if root_rendering_component.game_data.is_host() {
+            vec_of_nodes.extend(divqrcode::div_qr_code(root_rendering_component, bump));
+        }
+    }
+    if root_rendering_component.game_data.is_game_in_progress {
+        vec_of_nodes.push(dodrio!(bump,
+        <div class="div_clickable" onclick={move |root, vdom, _event| {
+                let rrc = root.unwrap_mut::<RootRenderingComponent>();
+                div_join_in_progress_on_click(rrc);
+                vdom.schedule_render();
+                }}>
+            <h2 style= "color:green;">
+                {vec![text(bumpalo::format!(in bump, "Join the game in progress!{}", "").into_bump_str())]}
+            </h2>
+        </div>
+        ));
+    }
+    if root_rendering_component.game_data.info_text != "" {
+        vec_of_nodes.push(dodrio!(bump,
+        <h2 style= "color:red;">
+            {vec![text(bumpalo::format!(in bump, "{}",
+            root_rendering_component.game_data.info_text).into_bump_str())]}
+        </h2>
+        ));
+    }
     //I don't know how to solve the lifetime problems. So I just clone the small data.
     let ff = root_rendering_component.game_data.content_folders.clone();
     for folder_name in ff {
@@ -59,25 +119,34 @@ pub fn div_invite_ask_begin_on_click(
     folder_name: &str,
     vdom_weak: dodrio::VdomWeak,
 ) {
+    //the new host after a host change invites in his room. Else the host creates a new room.
+    let is_host_of_room = rrc.game_data.is_host() && rrc.game_data.room_code != "";
+    divplayerprofile::save_my_profile(rrc);
+    rrc.game_data.host_ws_uid = rrc.game_data.my_ws_uid;
     rrc.game_data.my_player_number = 1;
     rrc.game_data.players.clear();
-    rrc.game_data.players.push(Player {
-        ws_uid: rrc.game_data.my_ws_uid,
-        points: 0,
-    });
+    let my_player = rrc.game_data.my_player();
+    rrc.game_data.players.push(my_player);
     rrc.game_data.game_status = GameStatus::InviteAsking;
     rrc.game_data.asked_folder_name = folder_name.to_string();
+    rrc.game_data.info_text = "".to_string();
 
     //async fetch_response() for gameconfig.json
     fetchgameconfig::fetch_game_config_request(rrc, vdom_weak);
-    //send the msg Invite
-    websocketcommunication::ws_send_msg(
-        &rrc.game_data.ws,
-        &WsMessage::Invite {
+    if !is_host_of_room {
+        //the join code will come in the msg RoomCreated
+        rrc.game_data.room_code = "".to_string();
+        //the host creates a new room. The invite is only for the members of the room.
+        rrc.game_data.send_msg(WsMessage::RoomCreate {
             my_ws_uid: rrc.game_data.my_ws_uid,
-            asked_folder_name: folder_name.to_string(),
-        },
-    );
+        });
+    }
+    //send the msg Invite
+    rrc.game_data.send_msg(WsMessage::Invite {
+        my_ws_uid: rrc.game_data.my_ws_uid,
+        asked_folder_name: folder_name.to_string(),
+    });
+    rrc.check_invalidate_for_all_components();
 }
 
 ///msg invite
@@ -85,19 +154,100 @@ pub fn on_msg_invite(
     rrc: &mut RootRenderingComponent,
     my_ws_uid: usize,
     asked_folder_name: String,
+    vdom_weak: dodrio::VdomWeak,
 ) {
     logmod::log1_str("rcv invite");
     rrc.reset();
     rrc.game_data.game_status = GameStatus::InviteAsked;
+    rrc.game_data.host_ws_uid = my_ws_uid;
     //the first player is the initiator
-    rrc.game_data.players.push(Player {
-        ws_uid: my_ws_uid,
-        points: 0,
-    });
-    rrc.game_data.players.push(Player {
-        ws_uid: rrc.game_data.my_ws_uid,
-        points: 0,
-    });
+    rrc.game_data.players.push(Player::new(my_ws_uid));
+    let my_player = rrc.game_data.my_player();
+    rrc.game_data.players.push(my_player);
     rrc.game_data.my_player_number = 2; //temporary number
     rrc.game_data.asked_folder_name = asked_folder_name;
+    rrc.check_invalidate_for_all_components();
+    //the avatars to choose from are in the game config of the content
+    fetchgameconfig::fetch_game_config_request(rrc, vdom_weak);
+}
+
+///msg joined in progress: the game content and config. The StateSnapshot with my cards comes next.
+pub fn on_msg_joined_in_progress(
+    rrc: &mut RootRenderingComponent,
+    asked_folder_name: String,
+    game_config: GameConfig,
+) {
+    logmod::log1_str(&format!("rcv joined in progress {}", asked_folder_name));
+    rrc.game_data.content_folder_name = asked_folder_name.clone();
+    rrc.game_data.asked_folder_name = asked_folder_name;
+    rrc.game_data.game_config = Some(game_config);
+    rrc.game_data.is_game_in_progress = false;
+    rrc.check_invalidate_for_all_components();
+}
+
+/// on click join the room with the join code from the input element
+pub fn div_join_room_on_click(rrc: &mut RootRenderingComponent) {
+    let window = unwrap!(web_sys::window(), "error: web_sys::window");
+    let document = unwrap!(window.document(), "error: window.document");
+    let input_room_code = unwrap!(unwrap!(
+        document.get_element_by_id("input_room_code"),
+        "No #input_room_code"
+    )
+    .dyn_into::<web_sys::HtmlInputElement>());
+    let room_code = input_room_code.value().trim().to_uppercase();
+    divplayerprofile::save_my_profile(rrc);
+    if room_code != "" {
+        rrc.game_data.info_text = "".to_string();
+        rrc.game_data.send_msg(WsMessage::RoomJoin {
+            my_ws_uid: rrc.game_data.my_ws_uid,
+            room_code,
+        });
+    }
+}
+
+/// on click join the game in progress of my room
+pub fn div_join_in_progress_on_click(rrc: &mut RootRenderingComponent) {
+    rrc.game_data.info_text = "".to_string();
+    divplayerprofile::save_my_profile(rrc);
+    rrc.game_data.send_msg(WsMessage::JoinInProgress {
+        my_ws_uid: rrc.game_data.my_ws_uid,
+        players: vec![rrc.game_data.my_player()],
+    });
+}
+
+///msg room created with the join code for the other players
+pub fn on_msg_room_created(rrc: &mut RootRenderingComponent, room_code: String) {
+    logmod::log1_str(&format!("rcv room created {}", room_code));
+    rrc.game_data.room_code = room_code;
+    rrc.check_invalidate_for_all_components();
+}
+
+///msg room joined, now wait for the invite or join the game in progress
+pub fn on_msg_room_joined(
+    rrc: &mut RootRenderingComponent,
+    room_code: String,
+    is_game_in_progress: bool,
+) {
+    logmod::log1_str(&format!("rcv room joined {}", room_code));
+    rrc.game_data.room_code = room_code;
+    rrc.game_data.is_game_in_progress = is_game_in_progress;
+    rrc.game_data.info_text = "".to_string();
+    rrc.check_invalidate_for_all_components();
+}
+
+///msg seat taken: a late member has taken over my seat while I was away.
+///I am back at the start and I can join the game in progress again.
+pub fn on_msg_seat_taken(rrc: &mut RootRenderingComponent, ws_uid: usize) {
+    logmod::log1_str(&format!("rcv seat taken by {}", ws_uid));
+    rrc.reset();
+    rrc.game_data.reset_game_seq();
+    rrc.game_data.is_game_in_progress = true;
+    rrc.game_data.info_text =
+        "While you were away a late player has taken over your seat.".to_string();
+}
+
+///msg room not found, the join code is wrong
+pub fn on_msg_room_not_found(rrc: &mut RootRenderingComponent, room_code: &str) {
+    logmod::log1_str(&format!("rcv room not found {}", room_code));
+    rrc.game_data.info_text = format!("Room {} not found.", room_code);
 }
@@ -2,8 +2,8 @@
 
 //region: use
 use crate::rootrenderingcomponent::RootRenderingComponent;
-use crate::websocketcommunication;
 use crate::logmod;
+use crate::divplayerprofile;
 
 use dodrio::builder::text;
 use dodrio::bumpalo::{self, Bump};
@@ -22,48 +22,99 @@ where
 {
     // 2S Click here to Accept play!
     logmod::log1_str("GameStatus::InviteAsked");
-    //return Click here to Accept play
+    //return my profile and Click here to Accept play
     dodrio!(bump,
-    <div class="div_clickable" onclick={move |root, vdom, _event| {
-                let rrc = root.unwrap_mut::<RootRenderingComponent>();
-                div_invite_asked_on_click(rrc);
-                vdom.schedule_render();
-            }}>
-        <h2 id= "ws_elem" style= "color:green;">
-                {vec![text(
-                    //show Ask Player2 to Play!
-                    bumpalo::format!(in bump, "Click here to Accept {}!", root_rendering_component.game_data.asked_folder_name)
-                        .into_bump_str(),
-                )]}
-        </h2>
+    <div>
+        {vec![divplayerprofile::div_player_profile(root_rendering_component, bump)]}
+        <div class="div_clickable" onclick={move |root, vdom, _event| {
+                    let rrc = root.unwrap_mut::<RootRenderingComponent>();
+                    div_invite_asked_on_click(rrc);
+                    vdom.schedule_render();
+                }}>
+            <h2 id= "ws_elem" style= "color:green;">
+                    {vec![text(
+                        //show Ask Player2 to Play!
+                        bumpalo::format!(in bump, "Click here to Accept {}!", root_rendering_component.game_data.asked_folder_name)
+                            .into_bump_str(),
+                    )]}
+            </h2>
+        </div>
+        <div class="div_clickable" onclick={move |root, vdom, _event| {
+                    let rrc = root.unwrap_mut::<RootRenderingComponent>();
+                    div_invite_decline_on_click(rrc);
+                    vdom.schedule_render();
+                }}>
+            <h2 style= "color:red;">
+                    {vec![text(bumpalo::format!(in bump, "Decline{}", "").into_bump_str())]}
+            </h2>
+        </div>
     </div>
     )
 }
 
 /// on click
 pub fn div_invite_asked_on_click(rrc: &mut RootRenderingComponent) {
+    //my name, color and avatar go with the accept
+    divplayerprofile::save_my_profile(rrc);
     rrc.game_data.game_status = GameStatus::PlayAccepted;
 
-    websocketcommunication::ws_send_msg(
-        &rrc.game_data.ws,
-        &WsMessage::PlayAccept {
-            my_ws_uid: rrc.game_data.my_ws_uid,
-            players: unwrap!(serde_json::to_string(&rrc.game_data.players)),
-        },
-    );
+    rrc.game_data.send_msg(WsMessage::PlayAccept {
+        my_ws_uid: rrc.game_data.my_ws_uid,
+        players: rrc.game_data.players.clone(),
+    });
 }
 
-///msg accept play
-pub fn on_msg_play_accept(rrc: &mut RootRenderingComponent, my_ws_uid: usize) {
-    if rrc.game_data.my_player_number == 1 {
-        rrc.game_data.players.push(Player {
-            ws_uid: my_ws_uid,
-            points: 0,
-        });
+/// on click decline the invite and wait for the next one
+pub fn div_invite_decline_on_click(rrc: &mut RootRenderingComponent) {
+    rrc.game_data.send_msg(WsMessage::InviteDecline {
+        my_ws_uid: rrc.game_data.my_ws_uid,
+    });
+    rrc.reset();
+}
+
+///msg invite cancel: the host has withdrawn the invite or it has expired.
+///Before the start of the game all go back to InviteAskBegin.
+pub fn on_msg_invite_cancel(rrc: &mut RootRenderingComponent, is_expired: bool) {
+    logmod::log1_str(&format!("rcv invite cancel expired {}", is_expired));
+    #[allow(clippy::wildcard_enum_match_arm)]
+    match rrc.game_data.game_status {
+        GameStatus::InviteAsking | GameStatus::InviteAsked | GameStatus::PlayAccepted => {
+            rrc.reset();
+            rrc.game_data.info_text = if is_expired {
+                "The invite has expired.".to_string()
+            } else {
+                "The host has cancelled the invite.".to_string()
+            };
+        }
+        _ => (),
+    }
+}
+
+///msg accept play. Only the host collects the players with their name, color and avatar.
+pub fn on_msg_play_accept(rrc: &mut RootRenderingComponent, my_ws_uid: usize, players: &[Player]) {
+    if rrc.game_data.is_host() && !rrc.game_data.players.iter().any(|p| p.ws_uid == my_ws_uid) {
+        let mut player = Player::new(my_ws_uid);
+        if let Some(profile) = players.iter().find(|p| p.ws_uid == my_ws_uid) {
+            player.set_profile(profile);
+        }
+        rrc.game_data.players.push(player);
         rrc.check_invalidate_for_all_components();
     }
 }
 
+///msg kicked from lobby: the host has removed me. I wait for the next invite in the room.
+pub fn on_msg_kicked_from_lobby(rrc: &mut RootRenderingComponent, host_ws_uid: usize) {
+    logmod::log1_str(&format!("rcv kicked from lobby by {}", host_ws_uid));
+    #[allow(clippy::wildcard_enum_match_arm)]
+    match rrc.game_data.game_status {
+        GameStatus::InviteAsked | GameStatus::PlayAccepted => {
+            rrc.reset();
+            rrc.game_data.info_text = "The host has removed you from the lobby.".to_string();
+        }
+        _ => (),
+    }
+}
+
 ///render play accepted
 pub fn div_play_accepted<'a, 'bump>(
     rrc: &'a RootRenderingComponent,
@@ -2,11 +2,13 @@
 
 //region: use
 use crate::rootrenderingcomponent::RootRenderingComponent;
+use crate::divplayerprofile;
+use crate::divqrcode;
 
 use dodrio::builder::text;
 use dodrio::bumpalo::{self, Bump};
 use dodrio::Node;
-use mem4_common::WsMessage;
+use mem4_common::{GameStatus, WsMessage};
 use typed_html::dodrio;
 //endregion
 
@@ -24,23 +26,14 @@ where
                     let rrc =
                         root.unwrap_mut::<RootRenderingComponent>();
                     //region: send WsMessage over WebSocket
+                    divplayerprofile::save_my_profile(rrc);
                     rrc.game_data_init();
 
-                    unwrap!(rrc
-                        .game_data
-                        .ws
-                        .send_with_str(
-                            &serde_json::to_string(&WsMessage::GameDataInit {
-        card_grid_data: unwrap!(serde_json::to_string(&rrc.game_data.card_grid_data)
-                    ,"serde_json::to_string(&self.game_data.card_grid_data)"),
-        players: unwrap!(serde_json::to_string(&rrc.game_data.players)
-                    ,"serde_json::to_string(&self.game_data.players)"),
-        game_config: unwrap!(serde_json::to_string(&rrc.game_data.game_config)
-                    ,"serde_json::to_string(&self.game_data.game_config)"),
-                })
-                .expect("error sending Invite"),
-            )
-            ,"Failed to send Invite");
+                    rrc.game_data.send_msg(WsMessage::GameDataInit {
+                        card_grid_data: rrc.game_data.card_grid_data.clone(),
+                        players: rrc.game_data.players.clone(),
+                        game_config: unwrap!(rrc.game_data.game_config.clone(),"rrc.game_data.game_config"),
+                    });
 
         //endregion
         vdom.schedule_render();
@@ -52,12 +45,160 @@ where
             </h2>
         </div>
         <div>
+            <h2 style= "color:red;">
+                {vec![
+                    text(bumpalo::format!(in bump, "Room code: {}", rrc.game_data.room_code).into_bump_str()),
+                ]}
+            </h2>
+            {divqrcode::div_qr_code(rrc, bump)}
             <h2 style= "color:red;">
                 {vec![
                     text(bumpalo::format!(in bump, "Players accepted: {}.", rrc.game_data.players.len()-1).into_bump_str()),
                 ]}
             </h2>
+            {div_lobby_players(rrc, bump)}
+        </div>
+        <div class="div_clickable" onclick={move |root, vdom, _event| {
+                    let rrc = root.unwrap_mut::<RootRenderingComponent>();
+                    div_invite_cancel_on_click(rrc);
+                    vdom.schedule_render();
+                }}>
+            <h2 style= "color:red;">
+                {vec![text(bumpalo::format!(in bump, "Cancel the invite{}", "").into_bump_str())]}
+            </h2>
         </div>
+        {vec![divplayerprofile::div_player_profile(rrc, bump)]}
     </div>
     )
 }
+
+/// on click the host withdraws the invite. The members go back to wait for the next one.
+pub fn div_invite_cancel_on_click(rrc: &mut RootRenderingComponent) {
+    rrc.game_data.send_msg(WsMessage::InviteCancel {
+        my_ws_uid: rrc.game_data.my_ws_uid,
+        is_expired: false,
+    });
+    rrc.reset();
+}
+
+///msg invite decline: the player does not play. If he was in my lobby, he leaves it.
+pub fn on_msg_invite_decline(rrc: &mut RootRenderingComponent, ws_uid: usize) {
+    if let GameStatus::InviteAsking = rrc.game_data.game_status {
+        match rrc
+            .game_data
+            .players
+            .iter()
+            .position(|p| p.ws_uid == ws_uid)
+        {
+            Some(index) if index > 0 => {
+                rrc.game_data.info_text = format!(
+                    "{} has declined the invite.",
+                    rrc.game_data.player_name(unwrap!(index.checked_add(1)))
+                );
+                rrc.game_data.players.remove(index);
+            }
+            _ => rrc.game_data.info_text = "A player has declined the invite.".to_string(),
+        }
+        rrc.check_invalidate_for_all_components();
+    }
+}
+
+///the players in the lobby with their name, color and avatar in the order of the turns.
+///The host moves the players to the order they sit around the table or removes them.
+///The host is always the first. The turns go round, so every seating order is possible.
+fn div_lobby_players<'a, 'bump>(
+    rrc: &'a RootRenderingComponent,
+    bump: &'bump Bump,
+) -> Vec<Node<'bump>>
+where
+    'a: 'bump,
+{
+    let mut vec_of_nodes = Vec::new();
+    let players_count = rrc.game_data.players.len();
+    for (index, player) in rrc.game_data.players.iter().enumerate() {
+        let style = bumpalo::format!(in bump, "color:{};", player.color).into_bump_str();
+        let mut buttons = Vec::new();
+        if index > 1 {
+            buttons.push(dodrio!(bump,
+            <span class="div_clickable lobby_button" onclick={move |root, vdom, _event| {
+                    let rrc = root.unwrap_mut::<RootRenderingComponent>();
+                    lobby_swap(rrc, unwrap!(index.checked_sub(1)));
+                    vdom.schedule_render();
+                    }}>
+                {vec![text("▲")]}
+            </span>
+            ));
+        }
+        if index > 0 && index < unwrap!(players_count.checked_sub(1)) {
+            buttons.push(dodrio!(bump,
+            <span class="div_clickable lobby_button" onclick={move |root, vdom, _event| {
+                    let rrc = root.unwrap_mut::<RootRenderingComponent>();
+                    lobby_swap(rrc, index);
+                    vdom.schedule_render();
+                    }}>
+                {vec![text("▼")]}
+            </span>
+            ));
+        }
+        if index > 0 {
+            buttons.push(dodrio!(bump,
+            <span class="div_clickable lobby_button" onclick={move |root, vdom, _event| {
+                    let rrc = root.unwrap_mut::<RootRenderingComponent>();
+                    lobby_kick(rrc, index);
+                    vdom.schedule_render();
+                    }}>
+                {vec![text("✕")]}
+            </span>
+            ));
+        }
+        vec_of_nodes.push(dodrio!(bump,
+        <h4 style={style}>
+            {divplayerprofile::img_avatar(bump, &player.avatar)}
+            {vec![text(bumpalo::format!(in bump, "{}. {} ", unwrap!(index.checked_add(1)), player.display_name(unwrap!(index.checked_add(1)))).into_bump_str())]}
+            {buttons}
+        </h4>
+        ));
+    }
+    vec_of_nodes
+}
+
+///swap the player with the next one in the order of the turns
+fn lobby_swap(rrc: &mut RootRenderingComponent, index: usize) {
+    if index > 0 && unwrap!(index.checked_add(1)) < rrc.game_data.players.len() {
+        rrc.game_data
+            .players
+            .swap(index, unwrap!(index.checked_add(1)));
+        send_lobby_changed(rrc);
+    }
+}
+
+///remove the player from the lobby. He goes back to wait for the next invite.
+fn lobby_kick(rrc: &mut RootRenderingComponent, index: usize) {
+    if index > 0 && index < rrc.game_data.players.len() {
+        rrc.game_data.players.remove(index);
+        send_lobby_changed(rrc);
+    }
+}
+
+///the server keeps the lobby for a new host and tells the removed players
+fn send_lobby_changed(rrc: &mut RootRenderingComponent) {
+    rrc.game_data.send_msg(WsMessage::LobbyChanged {
+        my_ws_uid: rrc.game_data.my_ws_uid,
+        lobby: rrc.game_data.players.iter().map(|p| p.ws_uid).collect(),
+    });
+    rrc.check_invalidate_for_all_components();
+}
+
+///msg lobby changed from the server: the old host has left the lobby with the host role.
+///Only the host shows the lobby, in the order of the server.
+pub fn on_msg_lobby_changed(rrc: &mut RootRenderingComponent, lobby: &[usize]) {
+    if let GameStatus::InviteAsking = rrc.game_data.game_status {
+        let players = lobby
+            .iter()
+            .filter_map(|uid| rrc.game_data.players.iter().find(|p| p.ws_uid == *uid))
+            .cloned()
+            .collect();
+        rrc.game_data.players = players;
+        rrc.check_invalidate_for_all_components();
+    }
+}
@@ -1,36 +1,161 @@
 //! statusplayagain.rs - code flow from this status
+//! The summary shows the ranking and the statistics of the round.
+//! The host offers the rematch with the same or a different content. The rounds make a best-of series.
 
 //region: use
 use crate::rootrenderingcomponent::RootRenderingComponent;
+use crate::statusinviteaskbegin;
 
 use dodrio::builder::text;
 use dodrio::bumpalo::{self, Bump};
 use dodrio::Node;
+use mem4_common::{Player, BEST_OF_ROUNDS};
 use typed_html::dodrio;
 //endregion
 
 ///play again
-pub fn div_play_again<'a, 'bump>(
-    _root_rendering_component: &'a RootRenderingComponent,
-    bump: &'bump Bump,
-) -> Node<'bump>
+pub fn div_play_again<'a, 'bump>(rrc: &'a RootRenderingComponent, bump: &'bump Bump) -> Node<'bump>
 where
     'a: 'bump,
 {
-    //end game ,Play again?  reload webpage
-    dodrio!(bump,
-    <div class="div_clickable" onclick={
-                move |root, vdom, _event| {
-                //reload the webpage
-                let window = unwrap!(web_sys::window(), "error: web_sys::window");
-                let x = window.location().reload();
-            }}>
-        <h2 id= "ws_elem" style= "color:green;">
-                {vec![text(
-                    //Play again?
-                    bumpalo::format!(in bump, "Game Over! Play again{}?", "").into_bump_str(),
-                )]}
+    let mut vec_of_nodes = Vec::new();
+    vec_of_nodes.push(dodrio!(bump,
+    <h2 style= "color:red;">
+        {vec![text(bumpalo::format!(in bump, "{}", series_text(rrc)).into_bump_str())]}
+    </h2>
+    ));
+    vec_of_nodes.push(div_summary(rrc, bump));
+    if rrc.game_data.is_host() {
+        //the rematch with the same or a different content. The players stay in the room.
+        let ff = rrc.game_data.content_folders.clone();
+        for folder_name in ff {
+            let folder_name_clone2 = folder_name.clone();
+            vec_of_nodes.push(dodrio!(bump,
+            <div class="div_clickable" onclick={move |root, vdom, _event| {
+                    let rrc = root.unwrap_mut::<RootRenderingComponent>();
+                    let v2= vdom.clone();
+                    statusinviteaskbegin::div_invite_ask_begin_on_click(rrc, &folder_name,v2);
+                    vdom.schedule_render();
+                    }}>
+                <h2 id= "ws_elem" style= "color:green;">
+                    {vec![text(
+                    bumpalo::format!(in bump, "Play again {} ?", folder_name_clone2)
+                        .into_bump_str(),
+                    )]}
+                </h2>
+            </div>
+            ));
+        }
+    } else {
+        vec_of_nodes.push(dodrio!(bump,
+        <h2 id= "ws_elem" style= "color:red;">
+            {vec![text(bumpalo::format!(in bump, "Wait for the host to play again{}.", "").into_bump_str())]}
         </h2>
+        ));
+    }
+    dodrio!(bump,
+    <div>
+        {vec_of_nodes}
+    </div>
+    )
+}
+
+///the summary of the round: the ranking and the statistics of every player
+fn div_summary<'a, 'bump>(rrc: &'a RootRenderingComponent, bump: &'bump Bump) -> Node<'bump>
+where
+    'a: 'bump,
+{
+    let players = &rrc.game_data.players;
+    let mut vec_of_nodes = Vec::new();
+    for header in &["Rank", "Player", "Pairs", "Mismatches", "Streak", "Time"] {
+        vec_of_nodes.push(dodrio!(bump,
+        <div class= "grid_item">
+            {vec![text(bumpalo::format!(in bump, "{}", header).into_bump_str())]}
+        </div>
+        ));
+    }
+    //the best first. Players with the same points have the same rank.
+    let mut ranking: Vec<usize> = (0..players.len()).collect();
+    ranking.sort_by(|a, b| {
+        unwrap!(players.get(*b))
+            .points
+            .cmp(&unwrap!(players.get(*a)).points)
+    });
+    for index in ranking {
+        let player = unwrap!(players.get(index));
+        let rank = unwrap!(players
+            .iter()
+            .filter(|p| p.points > player.points)
+            .count()
+            .checked_add(1));
+        let cells = vec![
+            format!("{}.", rank),
+            player.display_name(unwrap!(index.checked_add(1))),
+            format!("{}", player.points),
+            format!("{}", player.stats.mismatches),
+            format!("{}", player.stats.longest_streak),
+            format!(
+                "{}:{:02}",
+                unwrap!(player.stats.seconds.checked_div(60)),
+                unwrap!(player.stats.seconds.checked_rem(60))
+            ),
+        ];
+        let class = if rank == 1 {
+            "grid_item player_winner"
+        } else {
+            "grid_item"
+        };
+        for cell in cells {
+            vec_of_nodes.push(dodrio!(bump,
+            <div class={class}>
+                {vec![text(bumpalo::format!(in bump, "{}", cell).into_bump_str())]}
+            </div>
+            ));
+        }
+    }
+    dodrio!(bump,
+    <div class="grid_container_players" style= "grid-template-columns: auto auto auto auto auto auto;">
+        {vec_of_nodes}
     </div>
     )
 }
+
+///the winner or the winners on a tie, and the series
+fn series_text(rrc: &RootRenderingComponent) -> String {
+    let game_state = rrc.game_data.game_state();
+    let round_winners = names_of_best(&game_state.players, |p| p.points);
+    //more players with the max points is a tie
+    let mut series_text = if round_winners.len() > 1 {
+        format!(
+            "Game Over! It is a tie: {} win!",
+            round_winners.join(" and ")
+        )
+    } else {
+        format!("Game Over! {} wins!", round_winners.join(" and "))
+    };
+    series_text.push_str(&format!(
+        " Round {} of best of {}.",
+        game_state.round, BEST_OF_ROUNDS
+    ));
+    if game_state.is_series_over() {
+        series_text.push_str(&format!(
+            " {} won the series!",
+            names_of_best(&game_state.players, |p| p.rounds_won).join(" and ")
+        ));
+    }
+    series_text
+}
+
+///the names of the players with the best score, more of them on a tie
+fn names_of_best<F>(players: &[Player], score: F) -> Vec<String>
+where
+    F: Fn(&Player) -> usize,
+{
+    let best = players.iter().map(|p| score(p)).max().unwrap_or(0);
+    players
+        .iter()
+        .enumerate()
+        .filter(|(_index, p)| score(p) == best)
+        .map(|(index, p)| p.display_name(unwrap!(index.checked_add(1))))
+        .collect()
+}
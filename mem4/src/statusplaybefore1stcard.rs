@@ -1,11 +1,9 @@
 //! statusplaybefore1stcard.rs - code flow from this status
 
 //region: use
-use crate::gamedata::CardStatusCardFace;
 use crate::rootrenderingcomponent::RootRenderingComponent;
-use crate::websocketcommunication;
 use crate::logmod;
-use mem4_common::{GameStatus, WsMessage};
+use mem4_common::{GameAction, GameEvent, WsMessage};
 
 use dodrio::builder::text;
 use dodrio::bumpalo::{self, Bump};
@@ -27,7 +25,7 @@ where
         dodrio!(bump,
         <div >
             <h2 id= "ws_elem" style= "color:orange;">
-                {vec![text(bumpalo::format!(in bump, "Play player{} !", root_rendering_component.game_data.player_turn).into_bump_str())]}
+                {vec![text(bumpalo::format!(in bump, "Play {} !", root_rendering_component.game_data.player_name(root_rendering_component.game_data.player_turn)).into_bump_str())]}
             </h2>
         </div>
         )
@@ -35,7 +33,7 @@ where
         //return wait for the other player
         dodrio!(bump,
         <h2 id="ws_elem" style= "color:red;">
-            {vec![text(bumpalo::format!(in bump, "Wait for player{} !", root_rendering_component.game_data.player_turn).into_bump_str())]}
+            {vec![text(bumpalo::format!(in bump, "Wait for {} !", root_rendering_component.game_data.player_name(root_rendering_component.game_data.player_turn)).into_bump_str())]}
         </h2>
         )
     }
@@ -46,51 +44,35 @@ where
 /// on click
 pub fn on_click_1st_card(rrc: &mut RootRenderingComponent, this_click_card_index: usize) {
     logmod::log1_str("on_click_1st_card");
-    rrc.game_data.card_index_of_first_click = this_click_card_index;
     //change card status and game status
-    card_click_1st_card(rrc);
+    //the server will send back the authoritative state
+    card_click_1st_card(rrc, this_click_card_index);
     rrc.check_invalidate_for_all_components();
     //region: send WsMessage over WebSocket
-    websocketcommunication::ws_send_msg(
-        &rrc.game_data.ws,
-        &WsMessage::PlayerClick1stCard {
-            my_ws_uid: rrc.game_data.my_ws_uid,
-            players: unwrap!(serde_json::to_string(&rrc.game_data.players)),
-            card_grid_data: unwrap!(serde_json::to_string(&rrc.game_data.card_grid_data)),
-            game_status: rrc.game_data.game_status.clone(),
-            card_index_of_first_click: rrc.game_data.card_index_of_first_click,
-            card_index_of_second_click: rrc.game_data.card_index_of_second_click,
-        },
-    );
+    let msg_id = rrc.game_data.next_msg_id();
+    rrc.game_data.send_msg(WsMessage::PlayerClick1stCard {
+        my_ws_uid: rrc.game_data.my_ws_uid,
+        card_index_of_first_click: this_click_card_index,
+        events: Vec::new(),
+        seq: rrc.game_data.game_seq,
+        msg_id,
+        checksum: rrc.game_data.game_state().checksum(),
+    });
     //endregion
 }
 
-///on click
-pub fn card_click_1st_card(rrc: &mut RootRenderingComponent) {
+///on click the game engine flips the card up
+pub fn card_click_1st_card(rrc: &mut RootRenderingComponent, this_click_card_index: usize) {
     logmod::log1_str("card_click_1st_card");
-    //flip the card up
-    unwrap!(
-        rrc.game_data
-            .card_grid_data
-            .get_mut(rrc.game_data.card_index_of_first_click),
-        "error this_click_card_index"
-    )
-    .status = CardStatusCardFace::UpTemporary;
-    rrc.game_data.game_status = GameStatus::PlayBefore2ndCard;
+    rrc.game_data.apply_action(&GameAction::Click1stCard {
+        player_number: rrc.game_data.my_player_number,
+        card_index: this_click_card_index,
+    });
 }
 
-///msg player click
-pub fn on_msg_player_click_1st_card(
-    rrc: &mut RootRenderingComponent,
-    game_status: GameStatus,
-    card_grid_data: &str,
-    card_index_of_first_click: usize,
-    card_index_of_second_click: usize,
-) {
+///msg player click from the server with the changes of the state. Also the sender gets them.
+pub fn on_msg_player_click_1st_card(rrc: &mut RootRenderingComponent, events: &[GameEvent]) {
     logmod::log1_str("on_msg_player_click_1st_card");
-    rrc.game_data.game_status = game_status;
-    rrc.game_data.card_grid_data = unwrap!(serde_json::from_str(card_grid_data));
-    rrc.game_data.card_index_of_first_click = card_index_of_first_click;
-    rrc.game_data.card_index_of_second_click = card_index_of_second_click;
+    rrc.game_data.apply_events(events);
     rrc.check_invalidate_for_all_components();
 }
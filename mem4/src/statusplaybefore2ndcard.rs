@@ -1,11 +1,9 @@
 //! statusplaybefore2ndcard.rs - code flow from this status
 
 //region: use
-use crate::gamedata::CardStatusCardFace;
 use crate::rootrenderingcomponent::RootRenderingComponent;
-use crate::websocketcommunication;
 use crate::logmod;
-use mem4_common::{GameStatus, WsMessage};
+use mem4_common::{GameAction, GameEvent, WsMessage};
 
 use dodrio::builder::text;
 use dodrio::bumpalo::{self, Bump};
@@ -27,7 +25,7 @@ where
         dodrio!(bump,
         <div >
             <h2 id= "ws_elem" style= "color:orange;">
-                {vec![text(bumpalo::format!(in bump, "Play player{} !", root_rendering_component.game_data.player_turn).into_bump_str())]}
+                {vec![text(bumpalo::format!(in bump, "Play {} !", root_rendering_component.game_data.player_name(root_rendering_component.game_data.player_turn)).into_bump_str())]}
             </h2>
         </div>
         )
@@ -35,7 +33,7 @@ where
         //return wait for the other player
         dodrio!(bump,
         <h2 id="ws_elem" style= "color:red;">
-            {vec![text(bumpalo::format!(in bump, "Wait for player{} !", root_rendering_component.game_data.player_turn).into_bump_str())]}
+            {vec![text(bumpalo::format!(in bump, "Wait for {} !", root_rendering_component.game_data.player_name(root_rendering_component.game_data.player_turn)).into_bump_str())]}
         </h2>
         )
     }
@@ -46,166 +44,44 @@ where
 ///on click
 pub fn on_click_2nd_card(rrc: &mut RootRenderingComponent, this_click_card_index: usize) {
     logmod::log1_str("on_click_2nd_card");
-    rrc.game_data.card_index_of_second_click = this_click_card_index;
-    card_click_2nd_card(rrc);
+    //the outcome is calculated locally to show it immediately,
+    //but the server decides and sends back the authoritative state
+    card_click_2nd_card(rrc, this_click_card_index);
+    //region: send WsMessage over WebSocket
+    let msg_id = rrc.game_data.next_msg_id();
+    rrc.game_data.send_msg(WsMessage::PlayerClick2ndCard {
+        my_ws_uid: rrc.game_data.my_ws_uid,
+        card_index_of_second_click: this_click_card_index,
+        events: Vec::new(),
+        seq: rrc.game_data.game_seq,
+        msg_id,
+        checksum: rrc.game_data.game_state().checksum(),
+    });
+    //endregion
 }
 
 ///on second click
-///The on click event passed by JavaScript executes all the logic
+///The on click event passed by JavaScript executes all the logic of the game engine
 ///and changes only the fields of the Card Grid struct.
 ///That struct is the only permanent data storage for later render the virtual dom.
-pub fn card_click_2nd_card(rrc: &mut RootRenderingComponent) {
+pub fn card_click_2nd_card(rrc: &mut RootRenderingComponent, this_click_card_index: usize) {
     //3 possible outcomes: 1) same player, 2) Next Player 3) end game/play again
-    //flip the card up
-    unwrap!(
-        rrc.game_data
-            .card_grid_data
-            .get_mut(rrc.game_data.card_index_of_second_click),
-        "error this_click_card_index"
-    )
-    .status = CardStatusCardFace::UpTemporary;
-
-    //if the cards match, player get one point and continues another turn
-    if unwrap!(
-        rrc.game_data
-            .card_grid_data
-            .get(rrc.game_data.card_index_of_first_click),
-        "error game_data.card_index_of_first_click"
-    )
-    .card_number_and_img_src
-        == unwrap!(
-            rrc.game_data
-                .card_grid_data
-                .get(rrc.game_data.card_index_of_second_click),
-            "error game_data.card_index_of_second_click"
-        )
-        .card_number_and_img_src
-    {
-        //give points
-        unwrap!(
-            rrc.game_data
-                .players
-                .get_mut(unwrap!(rrc.game_data.player_turn.checked_sub(1))),
-            "rrc.game_data.players.get_mu(rrc.game_data.player_turn - 1)"
-        )
-        .points += 1;
-
-        // the two cards matches. make them permanent FaceUp
-        let x1 = rrc.game_data.card_index_of_first_click;
-        let x2 = rrc.game_data.card_index_of_second_click;
-        unwrap!(
-            rrc.game_data.card_grid_data.get_mut(x1),
-            "error game_data.card_index_of_first_click"
-        )
-        .status = CardStatusCardFace::UpPermanently;
-        unwrap!(
-            rrc.game_data.card_grid_data.get_mut(x2),
-            "error game_data.card_index_of_second_click"
-        )
-        .status = CardStatusCardFace::UpPermanently;
-        //if the sum of points is number of card/2, the game is over
-        let mut point_sum = 0;
-        for x in &rrc.game_data.players {
-            point_sum += x.points;
-        }
-        logmod::log1_str(
-            format!(
-                "card_grid len {}  point_sum {}",
-                rrc.game_data.card_grid_data.len(),
-                point_sum
-            )
-            .as_str(),
-        );
-        if unwrap!(rrc.game_data.card_grid_data.len().checked_div(2)) == point_sum {
-            //The game is over and the question Play again?
-            rrc.game_data.game_status = GameStatus::GameOverPlayAgainBegin;
-            //send message
-            unwrap!(
-                rrc.game_data.ws.send_with_str(
-                    &serde_json::to_string(&WsMessage::GameOverPlayAgainBegin {
-                        my_ws_uid: rrc.game_data.my_ws_uid,
-                        players: unwrap!(serde_json::to_string(&rrc.game_data.players)),
-                        card_grid_data: unwrap!(serde_json::to_string(
-                            &rrc.game_data.card_grid_data
-                        )),
-                        game_status: rrc.game_data.game_status.clone(),
-                        card_index_of_first_click: rrc.game_data.card_index_of_first_click,
-                        card_index_of_second_click: rrc.game_data.card_index_of_second_click,
-                    })
-                    .expect("error sending GameOverPlayAgainBegin"),
-                ),
-                "Failed to send GameOverPlayAgainBegin"
-            );
-        } else {
-            //the same player continues to play
-            rrc.game_data.game_status = GameStatus::PlayBefore1stCard;
-            //region: send WsMessage over WebSocket
-            websocketcommunication::ws_send_msg(
-                &rrc.game_data.ws,
-                &WsMessage::PlayerClick2ndCard {
-                    my_ws_uid: rrc.game_data.my_ws_uid,
-                    players: unwrap!(serde_json::to_string(&rrc.game_data.players)),
-                    card_grid_data: unwrap!(serde_json::to_string(&rrc.game_data.card_grid_data)),
-                    game_status: rrc.game_data.game_status.clone(),
-                    card_index_of_first_click: rrc.game_data.card_index_of_first_click,
-                    card_index_of_second_click: rrc.game_data.card_index_of_second_click,
-                },
-            );
-            //endregion
-        }
-    } else {
-        //if cards don't match
-        rrc.game_data.game_status = GameStatus::TakeTurnBegin;
-        //region: send WsMessage over WebSocket
-        websocketcommunication::ws_send_msg(
-            &rrc.game_data.ws,
-            &WsMessage::TakeTurnBegin {
-                my_ws_uid: rrc.game_data.my_ws_uid,
-                players: unwrap!(serde_json::to_string(&rrc.game_data.players)),
-                card_grid_data: unwrap!(serde_json::to_string(&rrc.game_data.card_grid_data)),
-                game_status: rrc.game_data.game_status.clone(),
-                card_index_of_first_click: rrc.game_data.card_index_of_first_click,
-                card_index_of_second_click: rrc.game_data.card_index_of_second_click,
-            },
-        );
-        //endregion
-    }
+    rrc.game_data.apply_action(&GameAction::Click2ndCard {
+        player_number: rrc.game_data.my_player_number,
+        card_index: this_click_card_index,
+    });
     rrc.check_invalidate_for_all_components();
 }
-///msg player click
-pub fn on_msg_player_click_2nd_card(
-    rrc: &mut RootRenderingComponent,
-    players: &str,
-    game_status: GameStatus,
-    card_grid_data: &str,
-    card_index_of_first_click: usize,
-    card_index_of_second_click: usize,
-) {
+///msg player click from the server with the changes of the state. Also the sender gets them.
+pub fn on_msg_player_click_2nd_card(rrc: &mut RootRenderingComponent, events: &[GameEvent]) {
     logmod::log1_str("on_msg_player_click_2nd_card");
-    //player point has changed
-    rrc.game_data.players = unwrap!(serde_json::from_str(players));
-    rrc.game_data.game_status = game_status;
-    rrc.game_data.card_grid_data = unwrap!(serde_json::from_str(card_grid_data));
-    rrc.game_data.card_index_of_first_click = card_index_of_first_click;
-    rrc.game_data.card_index_of_second_click = card_index_of_second_click;
+    rrc.game_data.apply_events(events);
     rrc.check_invalidate_for_all_components();
 }
 
-///msg player click
-pub fn on_msg_play_again(
-    rrc: &mut RootRenderingComponent,
-    players: &str,
-    game_status: GameStatus,
-    card_grid_data: &str,
-    card_index_of_first_click: usize,
-    card_index_of_second_click: usize,
-) {
+///msg game over from the server
+pub fn on_msg_play_again(rrc: &mut RootRenderingComponent, events: &[GameEvent]) {
     logmod::log1_str("on_msg_play_again");
-    //player point has changed
-    rrc.game_data.players = unwrap!(serde_json::from_str(players));
-    rrc.game_data.game_status = game_status;
-    rrc.game_data.card_grid_data = unwrap!(serde_json::from_str(card_grid_data));
-    rrc.game_data.card_index_of_first_click = card_index_of_first_click;
-    rrc.game_data.card_index_of_second_click = card_index_of_second_click;
+    rrc.game_data.apply_events(events);
     rrc.check_invalidate_for_all_components();
 }
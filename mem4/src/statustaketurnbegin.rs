@@ -3,8 +3,7 @@
 //region: use
 use crate::rootrenderingcomponent::RootRenderingComponent;
 //use crate::websocketcommunication;
-use mem4_common::{GameStatus, WsMessage};
-use crate::gamedata::{CardStatusCardFace};
+use mem4_common::{GameAction, GameEvent, WsMessage};
 use crate::logmod;
 
 use dodrio::builder::text;
@@ -26,34 +25,26 @@ where
         &rrc.game_data.my_player_number
     ));
     logmod::log1_str(&format!("player_turn {}", &rrc.game_data.player_turn));
-    let next_player = if rrc.game_data.player_turn < rrc.game_data.players.len() {
-        unwrap!(rrc.game_data.player_turn.checked_add(1))
-    } else {
-        1
-    };
+    //the absent players are skipped
+    let next_player = rrc.game_data.game_state().next_player();
     if rrc.game_data.my_player_number == next_player {
         dodrio!(bump,
         <div class="div_clickable" onclick={move |root, vdom, _event| {
                     let rrc =
                         root.unwrap_mut::<RootRenderingComponent>();
                     //this game_data mutable reference is dropped on the end of the function
+                    //the server will send back the authoritative state
+                    take_turn_end(rrc);
                     //region: send WsMessage over WebSocket
-                    unwrap!(rrc
-                        .game_data
-                        .ws
-                        .send_with_str(
-                            &serde_json::to_string(&WsMessage::TakeTurnEnd {
-                                my_ws_uid: rrc.game_data.my_ws_uid,
-                                players: unwrap!(serde_json::to_string(
-                                    &rrc.game_data.players,
-                                )
-                                ,"serde_json::to_string(&rrc.game_data.players)"),
-                            })
-                            .expect("error sending TakeTurnEnd"),
-                        )
-                        ,"Failed to send TakeTurnEnd");
+                    let msg_id = rrc.game_data.next_msg_id();
+                    rrc.game_data.send_msg(WsMessage::TakeTurnEnd {
+                        my_ws_uid: rrc.game_data.my_ws_uid,
+                        events: Vec::new(),
+                        seq: rrc.game_data.game_seq,
+                        msg_id,
+                        checksum: rrc.game_data.game_state().checksum(),
+                    });
                     //endregion
-                    take_turn_end(rrc);
                     // Finally, re-render the component on the next animation frame.
                     vdom.schedule_render();
                 }}>
@@ -69,57 +60,31 @@ where
         //return wait for the other player
         dodrio!(bump,
         <h2 id="ws_elem" style= "color:red;">
-            {vec![text(bumpalo::format!(in bump, "Wait for player{} !", next_player).into_bump_str())]}
+            {vec![text(bumpalo::format!(in bump, "Wait for {} !", rrc.game_data.player_name(next_player)).into_bump_str())]}
         </h2>
         )
     }
 }
 
-///fn on change for both click and we msg.
+///fn on click. The msg from the server brings the authoritative state.
 pub fn take_turn_end(rrc: &mut RootRenderingComponent) {
-    rrc.game_data.player_turn = if rrc.game_data.player_turn < rrc.game_data.players.len() {
-        unwrap!(rrc.game_data.player_turn.checked_add(1))
-    } else {
-        1
-    };
-
     //click on Change button closes first and second card
-    let x1 = rrc.game_data.card_index_of_first_click;
-    let x2 = rrc.game_data.card_index_of_second_click;
-    unwrap!(
-        rrc.game_data.card_grid_data.get_mut(x1),
-        "error game_data.card_index_of_first_click "
-    )
-    .status = CardStatusCardFace::Down;
-    unwrap!(
-        rrc.game_data.card_grid_data.get_mut(x2),
-        "error game_data.card_index_of_second_click"
-    )
-    .status = CardStatusCardFace::Down;
-    rrc.game_data.card_index_of_first_click = 0;
-    rrc.game_data.card_index_of_second_click = 0;
-    rrc.game_data.game_status = GameStatus::PlayBefore1stCard;
-
+    rrc.game_data.apply_action(&GameAction::TakeTurnEnd {
+        player_number: rrc.game_data.my_player_number,
+    });
     rrc.check_invalidate_for_all_components();
 }
 
 ///on msg take turn begin
-pub fn on_msg_take_turn_begin(
-    rrc: &mut RootRenderingComponent,
-    game_status: GameStatus,
-    card_grid_data: &str,
-    card_index_of_first_click: usize,
-    card_index_of_second_click: usize,
-) {
+pub fn on_msg_take_turn_begin(rrc: &mut RootRenderingComponent, events: &[GameEvent]) {
     logmod::log1_str("on_msg_take_turn_begin");
-    rrc.game_data.game_status = game_status;
-    rrc.game_data.card_grid_data = unwrap!(serde_json::from_str(card_grid_data));
-    rrc.game_data.card_index_of_first_click = card_index_of_first_click;
-    rrc.game_data.card_index_of_second_click = card_index_of_second_click;
+    rrc.game_data.apply_events(events);
     rrc.check_invalidate_for_all_components();
 }
 
 ///msg player change
-pub fn on_msg_take_turn_end(rrc: &mut RootRenderingComponent) {
-    take_turn_end(rrc);
+pub fn on_msg_take_turn_end(rrc: &mut RootRenderingComponent, events: &[GameEvent]) {
+    logmod::log1_str("on_msg_take_turn_end");
+    rrc.game_data.apply_events(events);
+    rrc.check_invalidate_for_all_components();
 }
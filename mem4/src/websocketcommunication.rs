@@ -1,28 +1,34 @@
 //! WebSocketcommunication.rs  - module that cares about WebSocket communication
 
 //region: use
+use crate::gamemsgsequence;
 use crate::rootrenderingcomponent::RootRenderingComponent;
 use crate::statusinviteasked;
+use crate::statusinviteasking;
 use crate::statusinviteaskbegin;
-use crate::statusplaybefore1stcard;
-use crate::statusplaybefore2ndcard;
-use crate::statustaketurnbegin;
 use crate::logmod;
+use crate::websocketreconnect;
 
 use futures::Future;
-use js_sys::Reflect;
-use mem4_common::GameStatus;
+use js_sys::{Reflect, Uint8Array};
+use mem4_common::{
+    GameStatus, ProtocolErrorCode, CAPABILITY_BINARY, CAPABILITY_SNAPSHOT, PROTOCOL_VERSION,
+};
 use mem4_common::WsMessage;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use web_sys::{console, ErrorEvent, WebSocket};
+use web_sys::{console, BinaryType, ErrorEvent, WebSocket};
 //endregion
 
 //the location_href is not consumed in this function and Clippy wants a reference instead a value
 //but I don't want references, because they have the lifetime problem.
 #[allow(clippy::needless_pass_by_value)]
-///setup WebSocket connection
-pub fn setup_ws_connection(location_href: String, client_ws_id: usize) -> WebSocket {
+///setup WebSocket connection. The client offers the binary msgs, unless only json is wanted for debugging.
+pub fn setup_ws_connection(
+    location_href: String,
+    session_token: &str,
+    is_json_only: bool,
+) -> WebSocket {
     //web-sys has WebSocket for Rust exactly like JavaScript has¸
     //location_href comes in this format  http://localhost:4000/
     let mut loc_href = location_href.replace("http://", "ws://").replace("https://", "wss://");
@@ -30,32 +36,39 @@ pub fn setup_ws_connection(location_href: String, client_ws_id: usize) -> WebSoc
     //let mut loc_href = String::from("ws://192.168.1.57:80/");
     loc_href.push_str("mem4ws/");
 
-    //send the client ws id as url_param for the first connect
-    //and reconnect on lost connection
-    loc_href.push_str(client_ws_id.to_string().as_str());
-    logmod::log1_str(&format!(
-        "location_href {}  loc_href {} client_ws_id {}",
-        location_href, loc_href, client_ws_id
-    ));
+    //for the first connect the url_param is "new". The server issues the ws_uid.
+    //for reconnect on lost connection the url_param is the session token.
+    if session_token.is_empty() {
+        loc_href.push_str("new");
+    } else {
+        loc_href.push_str(session_token);
+    }
+    logmod::log1_str(&format!("location_href {}", location_href));
 
     //same server address and port as http server
-    //for reconnect the old ws id will be an url param
     let ws = unwrap!(WebSocket::new(&loc_href), "WebSocket failed to connect.");
+    //the binary frames come as ArrayBuffer, not as Blob
+    ws.set_binary_type(BinaryType::Arraybuffer);
+    let mut capabilities = vec![CAPABILITY_SNAPSHOT.to_string()];
+    if !is_json_only {
+        capabilities.push(CAPABILITY_BINARY.to_string());
+    }
 
     //I don't know why is clone needed
     let ws_c = ws.clone();
     //It looks that the first send is in some way a handshake and is part of the connection
     //it will be execute on open as a closure
     let open_handler = Box::new(move || {
-        console::log_1(&"Connection opened, sending RequestWsUid to server".into());
+        console::log_1(&"Connection opened, sending Hello to server".into());
         unwrap!(
             ws_c.send_with_str(
-                &serde_json::to_string(&WsMessage::RequestWsUid {
-                    test: String::from("test"),
+                &serde_json::to_string(&WsMessage::Hello {
+                    protocol_version: PROTOCOL_VERSION,
+                    capabilities: capabilities.clone(),
                 })
-                .expect("error sending test"),
+                .expect("error sending Hello"),
             ),
-            "Failed to send 'test' to server"
+            "Failed to send Hello to server"
         );
     });
 
@@ -78,6 +91,8 @@ pub fn setup_ws_msg_recv(ws: &WebSocket, weak: dodrio::VdomWeak) {
     //Machine2 receives the WsMessage and runs the same code as the player would click. The RootRenderingComponent is blocked.
     //The method with_component() needs a future (promise) It will be executed on the next vdom tick.
     //This is the only way I found to write to RootRenderingComponent fields.
+    //the Pong is sent directly from the handler
+    let ws_c = ws.clone();
     let msg_recv_handler = Box::new(move |msg: JsValue| {
         let data: JsValue = unwrap!(
             Reflect::get(&msg, &"data".into()),
@@ -86,11 +101,24 @@ pub fn setup_ws_msg_recv(ws: &WebSocket, weak: dodrio::VdomWeak) {
 
         //serde_json can find out the variant of WsMessage
         //parse json and put data in the enum
-        let msg: WsMessage =
-            serde_json::from_str(&data.as_string().expect("Field 'data' is not string"))
-                .unwrap_or_else(|_x| WsMessage::Dummy {
-                    dummy: String::from("error"),
-                });
+        //a binary frame is bincode, if the connection has agreed on it
+        //a msg that cannot be parsed is handled like a ProtocolError
+        let msg: WsMessage = match data.as_string() {
+            Some(data) => {
+                serde_json::from_str(&data).unwrap_or_else(|e| WsMessage::ProtocolError {
+                    code: ProtocolErrorCode::ParseError,
+                    reason: e.to_string(),
+                    msg_type: WsMessage::msg_type_of_json(&data),
+                })
+            }
+            None => WsMessage::from_binary(&Uint8Array::new(&data).to_vec()).unwrap_or_else(|e| {
+                WsMessage::ProtocolError {
+                    code: ProtocolErrorCode::ParseError,
+                    reason: e,
+                    msg_type: "binary".to_string(),
+                }
+            }),
+        };
 
         //match enum by variant and prepares the future that will be executed on the next tick
         //in this big enum I put only boilerplate code that don't change any data.
@@ -98,25 +126,39 @@ pub fn setup_ws_msg_recv(ws: &WebSocket, weak: dodrio::VdomWeak) {
         match msg {
             //I don't know why I need a dummy, but is entertaining to have one.
             WsMessage::Dummy { dummy } => console::log_1(&dummy.into()),
-            //this RequestWsUid is only for the WebSocket server
-            WsMessage::RequestWsUid { test } => console::log_1(&test.into()),
-            WsMessage::ResponseWsUid { your_ws_uid } => {
+            //this Hello is only for the WebSocket server
+            WsMessage::Hello { .. } => console::log_1(&"Hello for the server".into()),
+            //the server checks that the connection is alive
+            WsMessage::Ping { ping_id } => {
+                //the server reads the json also on a binary connection
+                ws_send_msg(&ws_c, &WsMessage::Pong { ping_id }, false);
+                //the Ping is the clock for the resend timeout
+                wasm_bindgen_futures::spawn_local(
+                    weak.with_component(move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        gamemsgsequence::check_pending_game_msgs(root_rendering_component);
+                    })
+                    .map_err(|_| ()),
+                );
+            }
+            WsMessage::Pong { .. } => console::log_1(&"Pong for the server".into()),
+            WsMessage::PlayerLeft { ws_uid } => {
                 wasm_bindgen_futures::spawn_local(
                     weak.with_component({
+                        let v2 = weak.clone();
                         move |root| {
-                            logmod::log1_str(&format!("ResponseWsUid: {}  ", your_ws_uid));
                             let root_rendering_component =
                                 root.unwrap_mut::<RootRenderingComponent>();
-                            root_rendering_component.on_response_ws_uid(your_ws_uid);
+                            root_rendering_component.on_msg_player_left(ws_uid);
+                            v2.schedule_render();
                         }
                     })
                     .map_err(|_| ()),
                 );
             }
-
-            WsMessage::Invite {
-                my_ws_uid,
-                asked_folder_name,
+            WsMessage::HostChanged {
+                host_ws_uid,
+                players,
             } => {
                 wasm_bindgen_futures::spawn_local(
                     weak.with_component({
@@ -124,33 +166,63 @@ pub fn setup_ws_msg_recv(ws: &WebSocket, weak: dodrio::VdomWeak) {
                         move |root| {
                             let root_rendering_component =
                                 root.unwrap_mut::<RootRenderingComponent>();
-
-                            if let GameStatus::GameOverPlayAgainBegin
-                            | GameStatus::InviteAskBegin
-                            | GameStatus::InviteAsked =
-                                root_rendering_component.game_data.game_status
-                            {
-                                statusinviteaskbegin::on_msg_invite(
-                                    root_rendering_component,
-                                    my_ws_uid,
-                                    asked_folder_name,
-                                );
-                                v2.schedule_render();
-                            }
+                            root_rendering_component.on_msg_host_changed(
+                                host_ws_uid,
+                                players,
+                                v2.clone(),
+                            );
+                            v2.schedule_render();
                         }
                     })
                     .map_err(|_| ()),
                 );
             }
-            WsMessage::PlayAccept { my_ws_uid, .. } => {
+            WsMessage::PlayerRejoined { ws_uid } => {
                 wasm_bindgen_futures::spawn_local(
                     weak.with_component({
                         let v2 = weak.clone();
                         move |root| {
-                            console::log_1(&"rcv PlayAccept".into());
                             let root_rendering_component =
                                 root.unwrap_mut::<RootRenderingComponent>();
-                            statusinviteasked::on_msg_play_accept(
+                            root_rendering_component.on_msg_player_rejoined(ws_uid);
+                            v2.schedule_render();
+                        }
+                    })
+                    .map_err(|_| ()),
+                );
+            }
+            WsMessage::HelloRejected {
+                protocol_version,
+                reason,
+            } => {
+                wasm_bindgen_futures::spawn_local(
+                    weak.with_component({
+                        let v2 = weak.clone();
+                        move |root| {
+                            let root_rendering_component =
+                                root.unwrap_mut::<RootRenderingComponent>();
+                            root_rendering_component
+                                .on_msg_hello_rejected(protocol_version, &reason);
+                            v2.schedule_render();
+                        }
+                    })
+                    .map_err(|_| ()),
+                );
+            }
+            //these msgs are only for the WebSocket server
+            WsMessage::RoomCreate { .. }
+            | WsMessage::RoomJoin { .. }
+            | WsMessage::JoinInProgress { .. }
+            | WsMessage::ResendRequest { .. }
+            | WsMessage::StateRequest { .. } => console::log_1(&"room msg for the server".into()),
+            WsMessage::InviteDecline { my_ws_uid } => {
+                wasm_bindgen_futures::spawn_local(
+                    weak.with_component({
+                        let v2 = weak.clone();
+                        move |root| {
+                            let root_rendering_component =
+                                root.unwrap_mut::<RootRenderingComponent>();
+                            statusinviteasking::on_msg_invite_decline(
                                 root_rendering_component,
                                 my_ws_uid,
                             );
@@ -160,54 +232,33 @@ pub fn setup_ws_msg_recv(ws: &WebSocket, weak: dodrio::VdomWeak) {
                     .map_err(|_| ()),
                 );
             }
-            WsMessage::GameDataInit {
-                card_grid_data,
-                game_config,
-                players,
-            } => {
+            WsMessage::InviteCancel { is_expired, .. } => {
                 wasm_bindgen_futures::spawn_local(
                     weak.with_component({
                         let v2 = weak.clone();
                         move |root| {
                             let root_rendering_component =
                                 root.unwrap_mut::<RootRenderingComponent>();
-
-                            if let GameStatus::PlayAccepted =
-                                root_rendering_component.game_data.game_status
-                            {
-                                root_rendering_component.on_msg_game_data_init(
-                                    &card_grid_data,
-                                    &game_config,
-                                    &players,
-                                );
-                                v2.schedule_render();
-                            }
+                            statusinviteasked::on_msg_invite_cancel(
+                                root_rendering_component,
+                                is_expired,
+                            );
+                            v2.schedule_render();
                         }
                     })
                     .map_err(|_| ()),
                 );
             }
-            WsMessage::PlayerClick1stCard {
-                card_grid_data,
-                game_status,
-                card_index_of_first_click,
-                card_index_of_second_click,
-                ..
-            } => {
+            WsMessage::SeatTaken { ws_uid } => {
                 wasm_bindgen_futures::spawn_local(
                     weak.with_component({
                         let v2 = weak.clone();
-                        console::log_1(&"player_click".into());
                         move |root| {
                             let root_rendering_component =
                                 root.unwrap_mut::<RootRenderingComponent>();
-                            console::log_1(&"players".into());
-                            statusplaybefore1stcard::on_msg_player_click_1st_card(
+                            statusinviteaskbegin::on_msg_seat_taken(
                                 root_rendering_component,
-                                game_status,
-                                card_grid_data.as_str(),
-                                card_index_of_first_click,
-                                card_index_of_second_click,
+                                ws_uid,
                             );
                             v2.schedule_render();
                         }
@@ -215,29 +266,71 @@ pub fn setup_ws_msg_recv(ws: &WebSocket, weak: dodrio::VdomWeak) {
                     .map_err(|_| ()),
                 );
             }
-            WsMessage::PlayerClick2ndCard {
-                players,
-                card_grid_data,
-                game_status,
-                card_index_of_first_click,
-                card_index_of_second_click,
-                ..
+            WsMessage::LobbyChanged { lobby, .. } => {
+                wasm_bindgen_futures::spawn_local(
+                    weak.with_component({
+                        let v2 = weak.clone();
+                        move |root| {
+                            let root_rendering_component =
+                                root.unwrap_mut::<RootRenderingComponent>();
+                            statusinviteasking::on_msg_lobby_changed(
+                                root_rendering_component,
+                                &lobby,
+                            );
+                            v2.schedule_render();
+                        }
+                    })
+                    .map_err(|_| ()),
+                );
+            }
+            WsMessage::KickedFromLobby { host_ws_uid } => {
+                wasm_bindgen_futures::spawn_local(
+                    weak.with_component({
+                        let v2 = weak.clone();
+                        move |root| {
+                            let root_rendering_component =
+                                root.unwrap_mut::<RootRenderingComponent>();
+                            statusinviteasked::on_msg_kicked_from_lobby(
+                                root_rendering_component,
+                                host_ws_uid,
+                            );
+                            v2.schedule_render();
+                        }
+                    })
+                    .map_err(|_| ()),
+                );
+            }
+            WsMessage::RoomCreated { room_code } => {
+                wasm_bindgen_futures::spawn_local(
+                    weak.with_component({
+                        let v2 = weak.clone();
+                        move |root| {
+                            let root_rendering_component =
+                                root.unwrap_mut::<RootRenderingComponent>();
+                            statusinviteaskbegin::on_msg_room_created(
+                                root_rendering_component,
+                                room_code,
+                            );
+                            v2.schedule_render();
+                        }
+                    })
+                    .map_err(|_| ()),
+                );
+            }
+            WsMessage::RoomJoined {
+                room_code,
+                is_game_in_progress,
             } => {
                 wasm_bindgen_futures::spawn_local(
                     weak.with_component({
                         let v2 = weak.clone();
-                        console::log_1(&"player_click".into());
                         move |root| {
                             let root_rendering_component =
                                 root.unwrap_mut::<RootRenderingComponent>();
-                            console::log_1(&"players".into());
-                            statusplaybefore2ndcard::on_msg_player_click_2nd_card(
+                            statusinviteaskbegin::on_msg_room_joined(
                                 root_rendering_component,
-                                players.as_str(),
-                                game_status,
-                                card_grid_data.as_str(),
-                                card_index_of_first_click,
-                                card_index_of_second_click,
+                                room_code,
+                                is_game_in_progress,
                             );
                             v2.schedule_render();
                         }
@@ -245,27 +338,20 @@ pub fn setup_ws_msg_recv(ws: &WebSocket, weak: dodrio::VdomWeak) {
                     .map_err(|_| ()),
                 );
             }
-            WsMessage::TakeTurnBegin {
-                card_grid_data,
-                game_status,
-                card_index_of_first_click,
-                card_index_of_second_click,
-                ..
+            WsMessage::JoinedInProgress {
+                asked_folder_name,
+                game_config,
             } => {
                 wasm_bindgen_futures::spawn_local(
                     weak.with_component({
                         let v2 = weak.clone();
-                        console::log_1(&"take turn begin".into());
                         move |root| {
                             let root_rendering_component =
                                 root.unwrap_mut::<RootRenderingComponent>();
-                            console::log_1(&"players".into());
-                            statustaketurnbegin::on_msg_take_turn_begin(
+                            statusinviteaskbegin::on_msg_joined_in_progress(
                                 root_rendering_component,
-                                game_status,
-                                card_grid_data.as_str(),
-                                card_index_of_first_click,
-                                card_index_of_second_click,
+                                asked_folder_name,
+                                game_config,
                             );
                             v2.schedule_render();
                         }
@@ -273,44 +359,92 @@ pub fn setup_ws_msg_recv(ws: &WebSocket, weak: dodrio::VdomWeak) {
                     .map_err(|_| ()),
                 );
             }
-            WsMessage::TakeTurnEnd { .. } => {
+            WsMessage::RoomNotFound { room_code } => {
                 wasm_bindgen_futures::spawn_local(
                     weak.with_component({
                         let v2 = weak.clone();
                         move |root| {
                             let root_rendering_component =
                                 root.unwrap_mut::<RootRenderingComponent>();
-                            console::log_1(&"TakeTurnEnd".into());
-                            statustaketurnbegin::on_msg_take_turn_end(root_rendering_component);
+                            statusinviteaskbegin::on_msg_room_not_found(
+                                root_rendering_component,
+                                &room_code,
+                            );
                             v2.schedule_render();
                         }
                     })
                     .map_err(|_| ()),
                 );
             }
-            WsMessage::GameOverPlayAgainBegin {
-                players,
-                card_grid_data,
-                game_status,
-                card_index_of_first_click,
-                card_index_of_second_click,
-                ..
+            WsMessage::ResponseWsUid {
+                your_ws_uid,
+                session_token,
+                protocol_version,
+                capabilities,
+            } => {
+                wasm_bindgen_futures::spawn_local(
+                    weak.with_component({
+                        let v2 = weak.clone();
+                        move |root| {
+                            logmod::log1_str(&format!(
+                                "ResponseWsUid: {} protocol_version: {} capabilities: {:?}",
+                                your_ws_uid, protocol_version, capabilities
+                            ));
+                            let root_rendering_component =
+                                root.unwrap_mut::<RootRenderingComponent>();
+                            root_rendering_component.on_response_ws_uid(
+                                your_ws_uid,
+                                &session_token,
+                                &capabilities,
+                            );
+                            v2.schedule_render();
+                        }
+                    })
+                    .map_err(|_| ()),
+                );
+            }
+
+            WsMessage::Invite {
+                my_ws_uid,
+                asked_folder_name,
             } => {
                 wasm_bindgen_futures::spawn_local(
                     weak.with_component({
                         let v2 = weak.clone();
-                        console::log_1(&"play again".into());
                         move |root| {
                             let root_rendering_component =
                                 root.unwrap_mut::<RootRenderingComponent>();
-                            console::log_1(&"players".into());
-                            statusplaybefore2ndcard::on_msg_play_again(
+
+                            if let GameStatus::GameOverPlayAgainBegin
+                            | GameStatus::InviteAskBegin
+                            | GameStatus::InviteAsked =
+                                root_rendering_component.game_data.game_status
+                            {
+                                statusinviteaskbegin::on_msg_invite(
+                                    root_rendering_component,
+                                    my_ws_uid,
+                                    asked_folder_name,
+                                    v2.clone(),
+                                );
+                                v2.schedule_render();
+                            }
+                        }
+                    })
+                    .map_err(|_| ()),
+                );
+            }
+            WsMessage::PlayAccept { my_ws_uid, players } => {
+                wasm_bindgen_futures::spawn_local(
+                    weak.with_component({
+                        let v2 = weak.clone();
+                        move |root| {
+                            console::log_1(&"rcv PlayAccept".into());
+                            let root_rendering_component =
+                                root.unwrap_mut::<RootRenderingComponent>();
+                            statusinviteasked::on_msg_play_accept(
                                 root_rendering_component,
-                                players.as_str(),
-                                game_status,
-                                card_grid_data.as_str(),
-                                card_index_of_first_click,
-                                card_index_of_second_click,
+                                my_ws_uid,
+                                &players,
                             );
                             v2.schedule_render();
                         }
@@ -318,6 +452,72 @@ pub fn setup_ws_msg_recv(ws: &WebSocket, weak: dodrio::VdomWeak) {
                     .map_err(|_| ()),
                 );
             }
+            WsMessage::GameDataInit {
+                card_grid_data,
+                game_config,
+                players,
+            } => {
+                wasm_bindgen_futures::spawn_local(
+                    weak.with_component({
+                        let v2 = weak.clone();
+                        move |root| {
+                            let root_rendering_component =
+                                root.unwrap_mut::<RootRenderingComponent>();
+
+                            if let GameStatus::PlayAccepted =
+                                root_rendering_component.game_data.game_status
+                            {
+                                root_rendering_component.on_msg_game_data_init(
+                                    card_grid_data,
+                                    game_config,
+                                    players,
+                                );
+                                v2.schedule_render();
+                            }
+                        }
+                    })
+                    .map_err(|_| ()),
+                );
+            }
+            //the game msgs are applied in the order of their seq
+            game_msg @ WsMessage::PlayerClick1stCard { .. }
+            | game_msg @ WsMessage::PlayerClick2ndCard { .. }
+            | game_msg @ WsMessage::TakeTurnBegin { .. }
+            | game_msg @ WsMessage::TakeTurnEnd { .. }
+            | game_msg @ WsMessage::GameOverPlayAgainBegin { .. }
+            | game_msg @ WsMessage::StateSnapshot { .. } => {
+                wasm_bindgen_futures::spawn_local(
+                    weak.with_component({
+                        let v2 = weak.clone();
+                        move |root| {
+                            let root_rendering_component =
+                                root.unwrap_mut::<RootRenderingComponent>();
+                            gamemsgsequence::on_msg_game(root_rendering_component, game_msg);
+                            v2.schedule_render();
+                        }
+                    })
+                    .map_err(|_| ()),
+                );
+            }
+            WsMessage::ProtocolError {
+                code,
+                reason,
+                msg_type,
+            } => {
+                wasm_bindgen_futures::spawn_local(
+                    weak.with_component({
+                        let v2 = weak.clone();
+                        move |root| {
+                            let root_rendering_component =
+                                root.unwrap_mut::<RootRenderingComponent>();
+                            root_rendering_component
+                                .on_msg_protocol_error(&code, &reason, &msg_type);
+                            v2.schedule_render();
+                        }
+                    })
+                    .map_err(|_| ()),
+                );
+            }
         }
     });
 
@@ -328,43 +528,41 @@ pub fn setup_ws_msg_recv(ws: &WebSocket, weak: dodrio::VdomWeak) {
     //don't drop the event listener from memory
     cb_mrh.forget();
 }
-/// on error write it on the screen for debugging
-pub fn setup_ws_onerror(ws: &WebSocket, weak: dodrio::VdomWeak) {
+/// on error write it in the log for debugging
+///The close event that follows starts the reconnect.
+pub fn setup_ws_onerror(ws: &WebSocket) {
     let onerror_callback = Closure::wrap(Box::new(move |e: ErrorEvent| {
         let err_text = format!("error event {:?}", e);
         logmod::log1_str(&err_text);
-        {
-            wasm_bindgen_futures::spawn_local(
-                weak.with_component({
-                    let v2 = weak.clone();
-                    move |root| {
-                        console::log_1(&"error text".into());
-                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
-                        root_rendering_component.game_data.error_text = err_text;
-                        v2.schedule_render();
-                    }
-                })
-                .map_err(|_| ()),
-            );
-        }
     }) as Box<dyn FnMut(ErrorEvent)>);
     ws.set_onerror(Some(onerror_callback.as_ref().unchecked_ref()));
     onerror_callback.forget();
 }
 /// on close WebSocket connection
+///The client reconnects automatically. A fatal error, like a too old client, stops it.
 pub fn setup_ws_onclose(ws: &WebSocket, weak: dodrio::VdomWeak) {
+    let ws_c = ws.clone();
     let onclose_callback = Closure::wrap(Box::new(move |e: ErrorEvent| {
         let err_text = format!("ws_onclose {:?}", e);
         logmod::log1_str(&err_text);
         {
+            let ws_closed = ws_c.clone();
             wasm_bindgen_futures::spawn_local(
                 weak.with_component({
                     let v2 = weak.clone();
                     move |root| {
                         console::log_1(&"spawn_local because of vdom".into());
                         let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
-                        //I want to show a reconnect button to the user
-                        root_rendering_component.game_data.is_reconnect = true;
+                        //only the close of the current connection, not of an old one
+                        if root_rendering_component.game_data.ws == ws_closed
+                            && root_rendering_component.game_data.error_text == ""
+                        {
+                            root_rendering_component.game_data.is_reconnect = true;
+                            websocketreconnect::schedule_reconnect(
+                                root_rendering_component,
+                                v2.clone(),
+                            );
+                        }
                         v2.schedule_render();
                     }
                 })
@@ -381,19 +579,27 @@ pub fn setup_all_ws_events(ws: &WebSocket, weak: dodrio::VdomWeak) {
     setup_ws_msg_recv(ws, weak.clone());
 
     //WebSocket on error message callback
-    setup_ws_onerror(ws, weak.clone());
+    setup_ws_onerror(ws);
 
     //WebSocket on close message callback
     setup_ws_onclose(ws, weak);
 }
 
-///generic send ws message
-pub fn ws_send_msg(ws: &WebSocket, ws_message: &WsMessage) {
-    unwrap!(
+///generic send ws message. Returns false if the connection is not open.
+///The msgs of the game go through `GameData::send_msg` that keeps them in the outbox.
+///On a connection with the binary capability the msg is sent as bincode in a binary frame.
+pub fn ws_send_msg(ws: &WebSocket, ws_message: &WsMessage, is_binary: bool) -> bool {
+    //ready_state: 0	CONNECTING, 1	OPEN, 2	CLOSING, 3	CLOSED
+    if ws.ready_state() != 1 {
+        return false;
+    }
+    if is_binary {
+        ws.send_with_u8_array(&mut ws_message.to_binary()).is_ok()
+    } else {
         ws.send_with_str(&unwrap!(
             serde_json::to_string(ws_message),
             "error serde_json to_string WsMessage"
-        ),),
-        "Failed to send"
-    );
+        ))
+        .is_ok()
+    }
 }
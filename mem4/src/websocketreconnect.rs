@@ -1,4 +1,6 @@
 //! websocketreconnect.rs - reconnection for websocket must be part of the application.
+//! When the connection is lost the client reconnects automatically with exponential backoff.
+//! The msgs of the player wait in the outbox. The game stays usable and shows only a small indicator.
 
 //region: use
 use crate::rootrenderingcomponent::RootRenderingComponent;
@@ -8,50 +10,101 @@ use crate::logmod;
 use dodrio::builder::text;
 use dodrio::bumpalo::{self, Bump};
 use dodrio::Node;
+use futures::Future;
 use typed_html::dodrio;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+//endregion
 
-///render reconnect
-pub fn div_reconnect<'a, 'bump>(_rrc: &'a RootRenderingComponent, bump: &'bump Bump) -> Node<'bump>
+//region: const
+///milliseconds before the first reconnect attempt. Every failed attempt doubles it.
+const RECONNECT_FIRST_DELAY_MS: i32 = 1000;
+///the longest wait between two reconnect attempts
+const RECONNECT_MAX_DELAY_MS: i32 = 30_000;
+//endregion
+
+///render the reconnecting indicator. It does not block the game.
+pub fn div_reconnecting<'a, 'bump>(
+    rrc: &'a RootRenderingComponent,
+    bump: &'bump Bump,
+) -> Vec<Node<'bump>>
 where
     'a: 'bump,
 {
-    dodrio!(bump,
-    <div>
-        <div class="div_clickable" onclick={
-            move |root, vdom, _event| {
-            let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
-            //the old ws and closures are now a memory leak, but small
-            let window = unwrap!(web_sys::window(), "error: web_sys::window");
-            let href = root_rendering_component.game_data.href.clone();
-            //usize is Copy(), so I don't need clone()
-            let my_ws_uid = root_rendering_component.game_data.my_ws_uid;
-            logmod::log1_str(&format!(
-                "href {}  my_ws_uid {}",
-                href,
-                my_ws_uid,
-            ));
-            logmod::log1_str(&"before reconnect");
-            let ws = websocketcommunication::setup_ws_connection(href, my_ws_uid);
-            websocketcommunication::setup_all_ws_events(&ws,vdom.clone());
-
-            root_rendering_component.game_data.ws=ws;
-            logmod::log1_str(&"before game_data.is_reconnect = false and schedule_render");
-            root_rendering_component.game_data.is_reconnect = false;
-            vdom.schedule_render();
-        }}>
-            <h2 id= "ws_elem" style= "color:green;">
+    let mut vec_of_nodes = Vec::new();
+    if rrc.game_data.is_reconnect {
+        vec_of_nodes.push(dodrio!(bump,
+        <div class="div_clickable" onclick={move |root, vdom, _event| {
+                let rrc = root.unwrap_mut::<RootRenderingComponent>();
+                reconnect(rrc, vdom.clone());
+                vdom.schedule_render();
+                }}>
+            <h4 style= "color:orange;">
                 {vec![text(
-                //Reconnect?
-                bumpalo::format!(in bump, "Reconnect?{}", "").into_bump_str(),
+                bumpalo::format!(in bump, "reconnecting… {} msgs waiting. Click to reconnect now.",
+                rrc.game_data.outbox.len()).into_bump_str(),
                 )]}
-            </h2>
+            </h4>
         </div>
-        <h2 style= "color:red;">
-            {vec![text(
-                //connection lost
-                bumpalo::format!(in bump, "Connection lost.{}", "").into_bump_str(),
-            )]}
-        </h2>
-    </div>
-    )
+        ));
+    }
+    vec_of_nodes
+}
+
+///the connection is lost. The next attempt waits twice as long as the last one.
+pub fn schedule_reconnect(rrc: &mut RootRenderingComponent, vdom_weak: dodrio::VdomWeak) {
+    let delay = 2_i32
+        .checked_pow(rrc.game_data.reconnect_attempt)
+        .and_then(|factor| factor.checked_mul(RECONNECT_FIRST_DELAY_MS))
+        .map_or(RECONNECT_MAX_DELAY_MS, |delay| {
+            delay.min(RECONNECT_MAX_DELAY_MS)
+        });
+    rrc.game_data.reconnect_attempt = rrc.game_data.reconnect_attempt.saturating_add(1);
+    logmod::log1_str(&format!(
+        "reconnect attempt {} in {} ms",
+        rrc.game_data.reconnect_attempt, delay
+    ));
+    let callback = Closure::once_into_js(move || {
+        wasm_bindgen_futures::spawn_local(
+            vdom_weak
+                .with_component({
+                    let v2 = vdom_weak.clone();
+                    move |root| {
+                        let rrc = root.unwrap_mut::<RootRenderingComponent>();
+                        reconnect(rrc, v2.clone());
+                        v2.schedule_render();
+                    }
+                })
+                .map_err(|_| ()),
+        );
+    });
+    let window = unwrap!(web_sys::window(), "error: web_sys::window");
+    unwrap!(window
+        .set_timeout_with_callback_and_timeout_and_arguments_0(callback.unchecked_ref(), delay));
+}
+
+///open a new connection with my session token, if the old one is closed.
+///The outbox is sent when the server answers the Hello.
+pub fn reconnect(rrc: &mut RootRenderingComponent, vdom_weak: dodrio::VdomWeak) {
+    //ready_state: 0	CONNECTING, 1	OPEN, 2	CLOSING, 3	CLOSED
+    if !rrc.game_data.is_reconnect || rrc.game_data.ws.ready_state() != 3 {
+        return;
+    }
+    //the old ws and closures are now a memory leak, but small
+    let href = rrc.game_data.href.clone();
+    //only the session token can reconnect to my old ws_uid
+    let session_token = rrc.game_data.session_token.clone();
+    logmod::log1_str(&format!(
+        "reconnect href {}  my_ws_uid {}",
+        href, rrc.game_data.my_ws_uid,
+    ));
+    let ws = websocketcommunication::setup_ws_connection(
+        href,
+        &session_token,
+        rrc.game_data.is_json_only,
+    );
+    websocketcommunication::setup_all_ws_events(&ws, vdom_weak);
+    rrc.game_data.ws = ws;
+    //the new connection agrees on the binary msgs again in its Hello
+    rrc.game_data.is_binary = false;
 }
@@ -0,0 +1,974 @@
+//! gameengine.rs - the rules of the game without any web_sys, WebSocket or rendering.
+//! The wasm client and the server use the same rules.
+//! The transition takes the state and one action of a player and returns the new state and the events.
+
+//region: use
+use crate::{Card, CardStatusCardFace, GameConfig, GameStatus, Player, PlayerStats, BEST_OF_ROUNDS};
+use rand::seq::SliceRandom;
+use rand::Rng;
+//endregion
+
+//region: const
+///FNV-1a 64 bit offset basis for the checksum
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+///FNV-1a 64 bit prime for the checksum
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+//endregion
+
+///all the data of one game. The server keeps the authoritative copy.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GameState {
+    ///players in the order of the turns
+    pub players: Vec<Player>,
+    ///vector of cards
+    pub card_grid_data: Vec<Card>,
+    ///game status
+    pub game_status: GameStatus,
+    ///card index of first click
+    pub card_index_of_first_click: usize,
+    ///card index of second click
+    pub card_index_of_second_click: usize,
+    ///whose turn is now:  player 1,2,3,...
+    pub player_turn: usize,
+    ///round of the best-of series, 1 based
+    #[serde(default)]
+    pub round: usize,
+}
+
+///the actions of the players that change the game state
+#[derive(Serialize, Deserialize, Clone)]
+pub enum GameAction {
+    ///the player in turn clicks the first card
+    Click1stCard {
+        ///player 1,2,3,...
+        player_number: usize,
+        ///index of the clicked card
+        card_index: usize,
+    },
+    ///the player in turn clicks the second card
+    Click2ndCard {
+        ///player 1,2,3,...
+        player_number: usize,
+        ///index of the clicked card
+        card_index: usize,
+    },
+    ///the next player takes the turn
+    TakeTurnEnd {
+        ///player 1,2,3,...
+        player_number: usize,
+    },
+    ///the server skips the turn of the absent player in turn
+    SkipTurn,
+}
+
+///the smallest changes of the game state. Applied in order they change the old state into the new state.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum GameEvent {
+    ///the card has a new face status
+    CardFaceChanged {
+        ///card index
+        card_index: usize,
+        ///new status of the card
+        status: CardStatusCardFace,
+    },
+    ///the player has new points
+    PointsChanged {
+        ///player 1,2,3,...
+        player_number: usize,
+        ///new points
+        points: usize,
+    },
+    ///the clicked cards of this turn
+    ClickIndicesChanged {
+        ///card index of first click
+        card_index_of_first_click: usize,
+        ///card index of second click
+        card_index_of_second_click: usize,
+    },
+    ///the turn goes to another player
+    PlayerTurnChanged {
+        ///whose turn is now:  player 1,2,3,...
+        player_turn: usize,
+    },
+    ///the game has a new status
+    GameStatusChanged {
+        ///new game status
+        game_status: GameStatus,
+    },
+    ///the round is over and the score of the series has changed
+    SeriesScoreChanged {
+        ///player 1,2,3,...
+        player_number: usize,
+        ///points of the finished rounds
+        total_points: usize,
+        ///rounds won
+        rounds_won: usize,
+    },
+    ///the statistics of the player have changed
+    StatsChanged {
+        ///player 1,2,3,...
+        player_number: usize,
+        ///new statistics
+        stats: PlayerStats,
+    },
+    ///the player has left or rejoined the game
+    PlayerAbsenceChanged {
+        ///player 1,2,3,...
+        player_number: usize,
+        ///the player has lost the connection
+        is_absent: bool,
+    },
+}
+
+impl GameState {
+    ///constructor from the data of the msg GameDataInit. It is the first round of a new series.
+    pub fn new(card_grid_data: Vec<Card>, mut players: Vec<Player>) -> Self {
+        for player in &mut players {
+            player.points = 0;
+            player.is_absent = false;
+            player.total_points = 0;
+            player.rounds_won = 0;
+            player.stats = PlayerStats::default();
+        }
+        GameState {
+            players,
+            card_grid_data,
+            game_status: GameStatus::PlayBefore1stCard,
+            card_index_of_first_click: 0,
+            card_index_of_second_click: 0,
+            player_turn: 1,
+            round: 1,
+        }
+    }
+
+    ///the rematch continues the series of the last round: the totals are kept
+    ///and the starting player rotates
+    pub fn continue_series(&mut self, last_round: &GameState) {
+        self.round = unwrap!(last_round.round.checked_add(1));
+        for player in &mut self.players {
+            if let Some(last) = last_round
+                .players
+                .iter()
+                .find(|p| p.ws_uid == player.ws_uid)
+            {
+                player.total_points = last.total_points;
+                player.rounds_won = last.rounds_won;
+            }
+        }
+        if !self.players.is_empty() {
+            self.player_turn = unwrap!(unwrap!(
+                unwrap!(self.round.checked_sub(1)).checked_rem(self.players.len())
+            )
+            .checked_add(1));
+        }
+    }
+
+    ///the series is over after the last round or when a player has won the majority of the rounds
+    pub fn is_series_over(&self) -> bool {
+        self.round >= BEST_OF_ROUNDS
+            || self
+                .players
+                .iter()
+                .any(|p| unwrap!(p.rounds_won.checked_mul(2)) > BEST_OF_ROUNDS)
+    }
+
+    ///the player number (1 based) of this ws_uid
+    pub fn player_number(&self, ws_uid: usize) -> Option<usize> {
+        self.players
+            .iter()
+            .position(|p| p.ws_uid == ws_uid)
+            .and_then(|index| index.checked_add(1))
+    }
+
+    ///is this ws_uid a player in this game
+    pub fn is_player(&self, ws_uid: usize) -> bool {
+        self.player_number(ws_uid).is_some()
+    }
+
+    ///the next player after player_turn. The absent players are skipped.
+    ///If all the other players are absent, the same player continues.
+    pub fn next_player(&self) -> usize {
+        let mut next_player = self.player_turn;
+        for _i in 0..self.players.len() {
+            next_player = self.following_player(next_player);
+            if !self.is_absent(next_player) {
+                return next_player;
+            }
+        }
+        self.following_player(self.player_turn)
+    }
+
+    ///the player after this player in the order of the turns
+    fn following_player(&self, player_number: usize) -> usize {
+        if player_number < self.players.len() {
+            unwrap!(player_number.checked_add(1))
+        } else {
+            1
+        }
+    }
+
+    ///has this player lost the connection
+    pub fn is_absent(&self, player_number: usize) -> bool {
+        player_number
+            .checked_sub(1)
+            .and_then(|index| self.players.get(index))
+            .map_or(false, |p| p.is_absent)
+    }
+
+    ///the late player takes over the slot of the first absent player with his cards and points.
+    ///Returns the player number and the ws_uid of the absent player.
+    pub fn take_over_absent_player(&mut self, ws_uid: usize) -> Option<(usize, usize)> {
+        let index = self.players.iter().position(|p| p.is_absent)?;
+        let player = unwrap!(self.players.get_mut(index));
+        let absent_ws_uid = player.ws_uid;
+        player.ws_uid = ws_uid;
+        player.is_absent = false;
+        Some((unwrap!(index.checked_add(1)), absent_ws_uid))
+    }
+
+    ///the late player gets his own slice of newly dealt cards at the end of the grid
+    ///and the last place in the turns. Returns the player number.
+    ///The new slice has its own pairs, so it works only with an even number of cards in a slice.
+    pub fn add_player_with_new_cards<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        game_config: &GameConfig,
+        ws_uid: usize,
+    ) -> Result<usize, String> {
+        let slice_len = unwrap!(game_config
+            .grid_items_hor
+            .checked_mul(game_config.grid_items_ver));
+        //the slices of the players follow one another after the reserved index 0
+        let first_index = self.card_grid_data.len();
+        if first_index != unwrap!(unwrap!(self.players.len().checked_mul(slice_len)).checked_add(1))
+        {
+            return Err("the grid has no place for a new slice of cards".to_string());
+        }
+        for card in build_deck(rng, game_config, 1).into_iter().skip(1) {
+            self.card_grid_data.push(Card {
+                status: card.status,
+                card_number_and_img_src: card.card_number_and_img_src,
+                card_index_and_id: unwrap!(
+                    unwrap!(first_index.checked_sub(1)).checked_add(card.card_index_and_id)
+                ),
+            });
+        }
+        self.players.push(Player::new(ws_uid));
+        Ok(self.players.len())
+    }
+
+    ///the game waits for a click of the absent player in turn
+    pub fn is_turn_of_absent_player(&self) -> bool {
+        (self.game_status.as_ref() == GameStatus::PlayBefore1stCard.as_ref()
+            || self.game_status.as_ref() == GameStatus::PlayBefore2ndCard.as_ref())
+            && self.is_absent(self.player_turn)
+    }
+
+    ///checksum of the canonical game state: the cards, the points, the status and the turn.
+    ///The names, the play time and the absence of the players are not part of it.
+    ///FNV-1a over the text of the state gives the same result on the wasm client and on the server.
+    pub fn checksum(&self) -> u64 {
+        let mut canonical = format!(
+            "{};{};{};{};{};",
+            self.game_status.as_ref(),
+            self.card_index_of_first_click,
+            self.card_index_of_second_click,
+            self.player_turn,
+            self.round
+        );
+        for card in &self.card_grid_data {
+            canonical.push_str(&format!(
+                "{}:{}:{},",
+                card.card_index_and_id,
+                card.card_number_and_img_src,
+                card.status.as_ref()
+            ));
+        }
+        for player in &self.players {
+            canonical.push_str(&format!(
+                ";{}:{}:{}:{}",
+                player.ws_uid, player.points, player.total_points, player.rounds_won
+            ));
+        }
+        canonical.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+        })
+    }
+
+    ///the player has left or rejoined the game. Returns false if the ws_uid is not a player.
+    pub fn set_player_absent(&mut self, ws_uid: usize, is_absent: bool) -> bool {
+        match self.player_number(ws_uid) {
+            Some(player_number) => {
+                self.apply_event(&GameEvent::PlayerAbsenceChanged {
+                    player_number,
+                    is_absent,
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    ///apply one event to the state
+    pub fn apply_event(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::CardFaceChanged { card_index, status } => {
+                if let Some(card) = self.card_grid_data.get_mut(*card_index) {
+                    card.status = status.clone();
+                }
+            }
+            GameEvent::PointsChanged {
+                player_number,
+                points,
+            } => {
+                if let Some(player) = player_number
+                    .checked_sub(1)
+                    .and_then(|index| self.players.get_mut(index))
+                {
+                    player.points = *points;
+                }
+            }
+            GameEvent::ClickIndicesChanged {
+                card_index_of_first_click,
+                card_index_of_second_click,
+            } => {
+                self.card_index_of_first_click = *card_index_of_first_click;
+                self.card_index_of_second_click = *card_index_of_second_click;
+            }
+            GameEvent::PlayerTurnChanged { player_turn } => self.player_turn = *player_turn,
+            GameEvent::GameStatusChanged { game_status } => self.game_status = game_status.clone(),
+            GameEvent::SeriesScoreChanged {
+                player_number,
+                total_points,
+                rounds_won,
+            } => {
+                if let Some(player) = player_number
+                    .checked_sub(1)
+                    .and_then(|index| self.players.get_mut(index))
+                {
+                    player.total_points = *total_points;
+                    player.rounds_won = *rounds_won;
+                }
+            }
+            GameEvent::StatsChanged {
+                player_number,
+                stats,
+            } => {
+                if let Some(player) = player_number
+                    .checked_sub(1)
+                    .and_then(|index| self.players.get_mut(index))
+                {
+                    player.stats = stats.clone();
+                }
+            }
+            GameEvent::PlayerAbsenceChanged {
+                player_number,
+                is_absent,
+            } => {
+                if let Some(player) = player_number
+                    .checked_sub(1)
+                    .and_then(|index| self.players.get_mut(index))
+                {
+                    player.is_absent = *is_absent;
+                }
+            }
+        }
+    }
+
+    ///only the player in turn can click
+    fn check_player_turn(&self, player_number: usize) -> Result<(), String> {
+        if player_number == self.player_turn {
+            Ok(())
+        } else {
+            Err(format!(
+                "player {} is not the player in turn {}",
+                player_number, self.player_turn
+            ))
+        }
+    }
+
+    ///the click is valid only on an existing card face down. The index 0 is reserved.
+    fn check_card_face_down(&self, card_index: usize) -> Result<(), String> {
+        match self.card_grid_data.get(card_index) {
+            Some(card) if card_index != 0 => {
+                if let CardStatusCardFace::Down = card.status {
+                    Ok(())
+                } else {
+                    Err(format!("card {} is not face down", card_index))
+                }
+            }
+            _ => Err(format!("card index {} does not exist", card_index)),
+        }
+    }
+
+    ///the events of the first click
+    fn events_of_click_1st_card(
+        &self,
+        player_number: usize,
+        card_index: usize,
+    ) -> Result<Vec<GameEvent>, String> {
+        if self.game_status.as_ref() != GameStatus::PlayBefore1stCard.as_ref() {
+            return Err(format!("click 1st card in status {}", self.game_status));
+        }
+        self.check_player_turn(player_number)?;
+        self.check_card_face_down(card_index)?;
+
+        Ok(vec![
+            GameEvent::ClickIndicesChanged {
+                card_index_of_first_click: card_index,
+                card_index_of_second_click: self.card_index_of_second_click,
+            },
+            GameEvent::CardFaceChanged {
+                card_index,
+                status: CardStatusCardFace::UpTemporary,
+            },
+            GameEvent::GameStatusChanged {
+                game_status: GameStatus::PlayBefore2ndCard,
+            },
+        ])
+    }
+
+    ///the events of the second click
+    ///3 possible outcomes: 1) same player, 2) Next Player 3) end game/play again
+    fn events_of_click_2nd_card(
+        &self,
+        player_number: usize,
+        card_index: usize,
+    ) -> Result<Vec<GameEvent>, String> {
+        if self.game_status.as_ref() != GameStatus::PlayBefore2ndCard.as_ref() {
+            return Err(format!("click 2nd card in status {}", self.game_status));
+        }
+        self.check_player_turn(player_number)?;
+        self.check_card_face_down(card_index)?;
+
+        let x1 = self.card_index_of_first_click;
+        let x2 = card_index;
+        let mut stats = unwrap!(self.players.get(unwrap!(player_number.checked_sub(1))))
+            .stats
+            .clone();
+        let mut events = vec![GameEvent::ClickIndicesChanged {
+            card_index_of_first_click: x1,
+            card_index_of_second_click: x2,
+        }];
+
+        if unwrap!(self.card_grid_data.get(x1)).card_number_and_img_src
+            == unwrap!(self.card_grid_data.get(x2)).card_number_and_img_src
+        {
+            //the cards match: one point and the two cards are permanently face up
+            let points = unwrap!(
+                unwrap!(self.players.get(unwrap!(player_number.checked_sub(1))))
+                    .points
+                    .checked_add(1)
+            );
+            events.push(GameEvent::PointsChanged {
+                player_number,
+                points,
+            });
+            stats.streak = unwrap!(stats.streak.checked_add(1));
+            stats.longest_streak = std::cmp::max(stats.longest_streak, stats.streak);
+            events.push(GameEvent::StatsChanged {
+                player_number,
+                stats,
+            });
+            events.push(GameEvent::CardFaceChanged {
+                card_index: x1,
+                status: CardStatusCardFace::UpPermanently,
+            });
+            events.push(GameEvent::CardFaceChanged {
+                card_index: x2,
+                status: CardStatusCardFace::UpPermanently,
+            });
+
+            //if the sum of points is number of card/2, the game is over
+            let point_sum: usize = unwrap!(self
+                .players
+                .iter()
+                .map(|p| p.points)
+                .sum::<usize>()
+                .checked_add(1));
+            let game_status = if unwrap!(self.card_grid_data.len().checked_div(2)) == point_sum {
+                events.extend(self.events_of_round_over(player_number, points));
+                GameStatus::GameOverPlayAgainBegin
+            } else {
+                //the same player continues to play
+                GameStatus::PlayBefore1stCard
+            };
+            events.push(GameEvent::GameStatusChanged { game_status });
+        } else {
+            //the cards don't match, the next player will take the turn
+            stats.mismatches = unwrap!(stats.mismatches.checked_add(1));
+            stats.streak = 0;
+            events.push(GameEvent::StatsChanged {
+                player_number,
+                stats,
+            });
+            events.push(GameEvent::CardFaceChanged {
+                card_index: x2,
+                status: CardStatusCardFace::UpTemporary,
+            });
+            events.push(GameEvent::GameStatusChanged {
+                game_status: GameStatus::TakeTurnBegin,
+            });
+        }
+        Ok(events)
+    }
+
+    ///the events of the end of the round: the points go to the totals of the series
+    ///and the players with the most points win the round.
+    ///The last point of the player is not yet in the state.
+    fn events_of_round_over(&self, player_number: usize, points: usize) -> Vec<GameEvent> {
+        let points_of = |index: usize, p: &Player| {
+            if unwrap!(index.checked_add(1)) == player_number {
+                points
+            } else {
+                p.points
+            }
+        };
+        let max_points = self
+            .players
+            .iter()
+            .enumerate()
+            .map(|(index, p)| points_of(index, p))
+            .max()
+            .unwrap_or(0);
+        self.players
+            .iter()
+            .enumerate()
+            .map(|(index, p)| {
+                let round_points = points_of(index, p);
+                GameEvent::SeriesScoreChanged {
+                    player_number: unwrap!(index.checked_add(1)),
+                    total_points: unwrap!(p.total_points.checked_add(round_points)),
+                    rounds_won: if round_points == max_points {
+                        unwrap!(p.rounds_won.checked_add(1))
+                    } else {
+                        p.rounds_won
+                    },
+                }
+            })
+            .collect()
+    }
+
+    ///the events of take turn end: the two cards are flipped back face down.
+    fn events_of_take_turn_end(&self, player_number: usize) -> Result<Vec<GameEvent>, String> {
+        if self.game_status.as_ref() != GameStatus::TakeTurnBegin.as_ref() {
+            return Err(format!("take turn end in status {}", self.game_status));
+        }
+        let next_player = self.next_player();
+        if player_number != next_player {
+            return Err(format!(
+                "player {} is not the next player {}",
+                player_number, next_player
+            ));
+        }
+
+        Ok(vec![
+            GameEvent::PlayerTurnChanged {
+                player_turn: next_player,
+            },
+            GameEvent::CardFaceChanged {
+                card_index: self.card_index_of_first_click,
+                status: CardStatusCardFace::Down,
+            },
+            GameEvent::CardFaceChanged {
+                card_index: self.card_index_of_second_click,
+                status: CardStatusCardFace::Down,
+            },
+            GameEvent::ClickIndicesChanged {
+                card_index_of_first_click: 0,
+                card_index_of_second_click: 0,
+            },
+            GameEvent::GameStatusChanged {
+                game_status: GameStatus::PlayBefore1stCard,
+            },
+        ])
+    }
+
+    ///the events of skip turn: the card of the absent player is flipped back
+    ///and the next player plays.
+    fn events_of_skip_turn(&self) -> Result<Vec<GameEvent>, String> {
+        if !self.is_turn_of_absent_player() {
+            return Err(format!(
+                "skip turn of player {} in status {}",
+                self.player_turn, self.game_status
+            ));
+        }
+        let next_player = self.next_player();
+        if self.is_absent(next_player) {
+            return Err("all the players are absent".to_string());
+        }
+
+        let mut events = vec![GameEvent::PlayerTurnChanged {
+            player_turn: next_player,
+        }];
+        if self.game_status.as_ref() == GameStatus::PlayBefore2ndCard.as_ref() {
+            events.push(GameEvent::CardFaceChanged {
+                card_index: self.card_index_of_first_click,
+                status: CardStatusCardFace::Down,
+            });
+        }
+        events.push(GameEvent::ClickIndicesChanged {
+            card_index_of_first_click: 0,
+            card_index_of_second_click: 0,
+        });
+        events.push(GameEvent::GameStatusChanged {
+            game_status: GameStatus::PlayBefore1stCard,
+        });
+        Ok(events)
+    }
+}
+
+///the transition of the game: the state and one action return the new state and the events.
+///An action that does not respect the game rules returns an error and the state is not changed.
+pub fn transition(
+    state: &GameState,
+    action: &GameAction,
+) -> Result<(GameState, Vec<GameEvent>), String> {
+    let events = match *action {
+        GameAction::Click1stCard {
+            player_number,
+            card_index,
+        } => state.events_of_click_1st_card(player_number, card_index)?,
+        GameAction::Click2ndCard {
+            player_number,
+            card_index,
+        } => state.events_of_click_2nd_card(player_number, card_index)?,
+        GameAction::TakeTurnEnd { player_number } => {
+            state.events_of_take_turn_end(player_number)?
+        }
+        GameAction::SkipTurn => state.events_of_skip_turn()?,
+    };
+    let mut new_state = state.clone();
+    for event in &events {
+        new_state.apply_event(event);
+    }
+    Ok((new_state, events))
+}
+
+///build a new shuffled deck of cards for the number of players.
+///Every card has its pair. The index 0 is reserved for FaceDown and the cards start with base 1.
+pub fn build_deck<R: Rng>(
+    rng: &mut R,
+    game_config: &GameConfig,
+    players_count: usize,
+) -> Vec<Card> {
+    let item_count_minus_one = unwrap!(game_config.card_moniker.len().checked_sub(1));
+    let cards_count = unwrap!(players_count.checked_mul(unwrap!(game_config
+        .grid_items_hor
+        .checked_mul(game_config.grid_items_ver))));
+    let random_count = unwrap!(cards_count.checked_div(2));
+    //if the number of cards is bigger than the images, i choose all the images.
+    //for the rest I use random.
+    //integer division rounds toward zero
+    let multiple: usize = unwrap!(random_count.checked_div(item_count_minus_one));
+    let rest =
+        unwrap!(random_count.checked_sub(unwrap!(item_count_minus_one.checked_mul(multiple))));
+
+    //region: find random numbers between 1 and item_count
+    let mut vec_of_random_numbers = Vec::new();
+    for _i in 1..=rest {
+        //how to avoid duplicates
+        let mut num: usize;
+        // a do-while is written as a  loop-break
+        loop {
+            //gen_range is lower inclusive, upper exclusive 26 + 1
+            num = rng.gen_range(1, unwrap!(item_count_minus_one.checked_add(1)));
+            if !vec_of_random_numbers.contains(&num) {
+                break;
+            }
+        }
+        //push a pair of the same number
+        vec_of_random_numbers.push(num);
+        vec_of_random_numbers.push(num);
+    }
+    for _m in 1..=multiple {
+        for i in 1..=item_count_minus_one {
+            vec_of_random_numbers.push(i);
+            vec_of_random_numbers.push(i);
+        }
+    }
+    //endregion
+
+    //shuffle the numbers
+    vec_of_random_numbers.as_mut_slice().shuffle(rng);
+
+    //region: create Cards from random numbers
+    let mut card_grid_data = Vec::new();
+    //Index 0 is special and reserved for FaceDown. Cards start with base 1
+    card_grid_data.push(Card {
+        status: CardStatusCardFace::Down,
+        card_number_and_img_src: 0,
+        card_index_and_id: 0,
+    });
+    //create cards and push to the vector
+    for (index, random_number) in vec_of_random_numbers.iter().enumerate() {
+        card_grid_data.push(Card {
+            status: CardStatusCardFace::Down,
+            //dereference random number from iterator
+            card_number_and_img_src: *random_number,
+            //card base index will be 1. 0 is reserved for FaceDown.
+            card_index_and_id: unwrap!(index.checked_add(1), "usize overflow"),
+        });
+    }
+    //endregion
+    card_grid_data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    ///2 players and 2 pairs: the cards 1 and 3 match, the cards 2 and 4 match
+    fn new_state() -> GameState {
+        let mut card_grid_data = vec![Card {
+            status: CardStatusCardFace::Down,
+            card_number_and_img_src: 0,
+            card_index_and_id: 0,
+        }];
+        for (index, card_number) in [1, 2, 1, 2].iter().enumerate() {
+            card_grid_data.push(Card {
+                status: CardStatusCardFace::Down,
+                card_number_and_img_src: *card_number,
+                card_index_and_id: unwrap!(index.checked_add(1)),
+            });
+        }
+        GameState::new(card_grid_data, vec![Player::new(11), Player::new(22)])
+    }
+
+    fn new_game_config() -> GameConfig {
+        GameConfig {
+            card_moniker: vec![
+                String::new(),
+                "A".to_string(),
+                "B".to_string(),
+                "C".to_string(),
+            ],
+            img_filename: Vec::new(),
+            sound_filename: Vec::new(),
+            card_width: 100,
+            card_height: 100,
+            grid_items_hor: 4,
+            grid_items_ver: 2,
+        }
+    }
+
+    fn click(state: &GameState, action: &GameAction) -> GameState {
+        unwrap!(transition(state, action)).0
+    }
+
+    fn card_status(state: &GameState, card_index: usize) -> &str {
+        unwrap!(state.card_grid_data.get(card_index))
+            .status
+            .as_ref()
+    }
+
+    #[test]
+    fn transition_match() {
+        let state_1 = click(
+            &new_state(),
+            &GameAction::Click1stCard {
+                player_number: 1,
+                card_index: 1,
+            },
+        );
+        assert_eq!(
+            state_1.game_status.as_ref(),
+            GameStatus::PlayBefore2ndCard.as_ref()
+        );
+        assert_eq!(
+            card_status(&state_1, 1),
+            CardStatusCardFace::UpTemporary.as_ref()
+        );
+
+        let state_2 = click(
+            &state_1,
+            &GameAction::Click2ndCard {
+                player_number: 1,
+                card_index: 3,
+            },
+        );
+        assert_eq!(
+            state_2.game_status.as_ref(),
+            GameStatus::PlayBefore1stCard.as_ref()
+        );
+        assert_eq!(state_2.player_turn, 1);
+        assert_eq!(unwrap!(state_2.players.first()).points, 1);
+        assert_eq!(
+            card_status(&state_2, 1),
+            CardStatusCardFace::UpPermanently.as_ref()
+        );
+        assert_eq!(
+            card_status(&state_2, 3),
+            CardStatusCardFace::UpPermanently.as_ref()
+        );
+    }
+
+    #[test]
+    fn transition_mismatch() {
+        let state_1 = click(
+            &new_state(),
+            &GameAction::Click1stCard {
+                player_number: 1,
+                card_index: 1,
+            },
+        );
+        let state_2 = click(
+            &state_1,
+            &GameAction::Click2ndCard {
+                player_number: 1,
+                card_index: 2,
+            },
+        );
+        assert_eq!(
+            state_2.game_status.as_ref(),
+            GameStatus::TakeTurnBegin.as_ref()
+        );
+        assert_eq!(unwrap!(state_2.players.first()).points, 0);
+        assert_eq!(unwrap!(state_2.players.first()).stats.mismatches, 1);
+
+        let state_3 = click(&state_2, &GameAction::TakeTurnEnd { player_number: 2 });
+        assert_eq!(
+            state_3.game_status.as_ref(),
+            GameStatus::PlayBefore1stCard.as_ref()
+        );
+        assert_eq!(state_3.player_turn, 2);
+        assert_eq!(card_status(&state_3, 1), CardStatusCardFace::Down.as_ref());
+        assert_eq!(card_status(&state_3, 2), CardStatusCardFace::Down.as_ref());
+    }
+
+    #[test]
+    fn transition_last_pair_ends_the_game() {
+        let mut state = new_state();
+        for (card_1, card_2) in &[(1, 3), (2, 4)] {
+            state = click(
+                &state,
+                &GameAction::Click1stCard {
+                    player_number: 1,
+                    card_index: *card_1,
+                },
+            );
+            state = click(
+                &state,
+                &GameAction::Click2ndCard {
+                    player_number: 1,
+                    card_index: *card_2,
+                },
+            );
+        }
+        assert_eq!(
+            state.game_status.as_ref(),
+            GameStatus::GameOverPlayAgainBegin.as_ref()
+        );
+        let winner = unwrap!(state.players.first());
+        assert_eq!(winner.points, 2);
+        assert_eq!(winner.total_points, 2);
+        assert_eq!(winner.rounds_won, 1);
+        assert_eq!(unwrap!(state.players.get(1)).rounds_won, 0);
+    }
+
+    #[test]
+    fn transition_rejects_invalid_clicks() {
+        let state = new_state();
+        //not the turn of player 2
+        assert!(transition(
+            &state,
+            &GameAction::Click1stCard {
+                player_number: 2,
+                card_index: 1,
+            }
+        )
+        .is_err());
+        //the card is already face up
+        let state_1 = click(
+            &state,
+            &GameAction::Click1stCard {
+                player_number: 1,
+                card_index: 1,
+            },
+        );
+        assert!(transition(
+            &state_1,
+            &GameAction::Click2ndCard {
+                player_number: 1,
+                card_index: 1,
+            }
+        )
+        .is_err());
+        //the reserved index 0
+        assert!(transition(
+            &state_1,
+            &GameAction::Click2ndCard {
+                player_number: 1,
+                card_index: 0,
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn next_player_goes_round() {
+        let mut state = new_state();
+        assert_eq!(state.next_player(), 2);
+        state.player_turn = 2;
+        assert_eq!(state.next_player(), 1);
+    }
+
+    #[test]
+    fn next_player_skips_absent_players() {
+        let mut state = new_state();
+        state.players.push(Player::new(33));
+        assert_eq!(state.next_player(), 2);
+        assert!(state.set_player_absent(22, true));
+        assert_eq!(state.next_player(), 3);
+        assert!(state.set_player_absent(33, true));
+        //all the other players are absent, the same player continues
+        assert_eq!(state.next_player(), 1);
+        state.player_turn = 3;
+        assert_eq!(state.next_player(), 1);
+    }
+
+    #[test]
+    fn build_deck_has_exact_pairs() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let game_config = new_game_config();
+        for players_count in 1..=3 {
+            let deck = build_deck(&mut rng, &game_config, players_count);
+            assert_eq!(
+                deck.len(),
+                unwrap!(unwrap!(players_count.checked_mul(8)).checked_add(1))
+            );
+            assert_eq!(unwrap!(deck.first()).card_number_and_img_src, 0);
+            for (index, card) in deck.iter().enumerate() {
+                assert_eq!(card.card_index_and_id, index);
+            }
+            for card in deck.iter().skip(1) {
+                let count = deck
+                    .iter()
+                    .skip(1)
+                    .filter(|c| c.card_number_and_img_src == card.card_number_and_img_src)
+                    .count();
+                assert_eq!(count.checked_rem(2), Some(0));
+                assert!(card.card_number_and_img_src >= 1);
+                assert!(card.card_number_and_img_src < game_config.card_moniker.len());
+            }
+        }
+    }
+
+    #[test]
+    fn checksum_of_the_same_state() {
+        let state = new_state();
+        assert_eq!(state.checksum(), new_state().checksum());
+        let state_copy = state.clone();
+        assert_eq!(state.checksum(), state_copy.checksum());
+        let state_2 = click(
+            &state,
+            &GameAction::Click1stCard {
+                player_number: 1,
+                card_index: 1,
+            },
+        );
+        assert_ne!(state.checksum(), state_2.checksum());
+    }
+}
@@ -7,6 +7,10 @@
 //! ## mem4_common
 //! Learning to code Rust for a http + WebSocket.  
 //! Here are just the structures, that are in common between frontend and backend.  
+//! The game engine has the rules of the game without web_sys or rendering: the deck builder  
+//! and the transition that takes the state and an action and returns the new state and the events.  
+//! The wasm client and the server use the same rules.  
+//! The WsMessage is json in text frames or, if both sides agree, bincode in binary frames.  
 //!
 //!
 //!
@@ -44,126 +48,404 @@
 //endregion
 
 //region: extern and use statements
+extern crate bincode;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate rand;
 extern crate serde_json;
 extern crate strum_macros;
+#[macro_use]
+extern crate unwrap;
 
 use strum_macros::{Display, AsRefStr};
 //endregion
 
+//region: const
+///the version of the protocol. Increase it when the msgs change.
+pub const PROTOCOL_VERSION: usize = 2;
+///the oldest protocol version that the server still accepts.
+///Version 2 sends the changes of the game state instead of the whole state.
+pub const MIN_PROTOCOL_VERSION: usize = 2;
+///capability: the client can apply the msg StateSnapshot after reconnect
+pub const CAPABILITY_SNAPSHOT: &str = "snapshot";
+///capability: the msgs of this connection are bincode in binary frames instead of json in text frames
+pub const CAPABILITY_BINARY: &str = "bincode";
+///the rematch series is best of this number of rounds
+pub const BEST_OF_ROUNDS: usize = 3;
+///the colors a player can choose
+pub const PLAYER_COLORS: [&str; 6] = [
+    "#ffb3b3", "#b3d9ff", "#b3ffb3", "#ffe0a3", "#dcc6ff", "#ffc6e8",
+];
+///the longest name of a player in chars
+pub const PLAYER_NAME_MAX_CHARS: usize = 12;
+//endregion
+
+//region: mod
+mod gameengine;
+pub use gameengine::{build_deck, transition, GameAction, GameEvent, GameState};
+//endregion
+
 ///`WsMessage` enum for WebSocket
-#[derive(Serialize, Deserialize)]
+///The name of the variant is the msg type.
+#[derive(Serialize, Deserialize, AsRefStr, Clone)]
 pub enum WsMessage {
     ///Dummy
     Dummy {
         ///anything
         dummy: String,
     },
-    ///Request WebSocket Uid - first message to WebSocket server
-    RequestWsUid {
-        ///anything
-        test: String,
+    ///the handshake - first message to WebSocket server
+    Hello {
+        ///protocol version of the client
+        protocol_version: usize,
+        ///optional features of the client
+        capabilities: Vec<String>,
     },
-    ///response from WebSocket server for first message
+    ///response from WebSocket server when the Hello is accepted
     ResponseWsUid {
-        ///WebSocket Uid
+        ///WebSocket Uid issued by the server
         your_ws_uid: usize,
+        ///secret token of the session. Only with this token the client can reconnect.
+        session_token: String,
+        ///the protocol version for this connection. Can be lower than the client version.
+        protocol_version: usize,
+        ///the capabilities of the client that the server accepts
+        capabilities: Vec<String>,
+    },
+    ///response from WebSocket server when the client is too old
+    HelloRejected {
+        ///protocol version of the server
+        protocol_version: usize,
+        ///human readable reason
+        reason: String,
+    },
+    ///the server checks periodically if the connection is alive
+    Ping {
+        ///the client returns the same id in the Pong
+        ping_id: usize,
+    },
+    ///the client answers to Ping
+    Pong {
+        ///the same id as in the Ping
+        ping_id: usize,
+    },
+    ///a member of the room wants to join the game in progress
+    JoinInProgress {
+        ///ws client instance unique id.
+        my_ws_uid: usize,
+        ///my player with the name, color and avatar
+        #[serde(default)]
+        players: Vec<Player>,
+    },
+    ///response to JoinInProgress. The StateSnapshot with the new state comes next to all players.
+    JoinedInProgress {
+        ///the content folder of the game
+        asked_folder_name: String,
+        ///game config
+        game_config: GameConfig,
+    },
+    ///the server notifies the other members of the room that a player has lost the connection.
+    ///The turns skip the absent player.
+    PlayerLeft {
+        ///ws_uid of the absent player
+        ws_uid: usize,
+    },
+    ///the host role has passed to another member of the room, because the host has lost the connection.
+    ///The new host gets the lobby and can start the game.
+    HostChanged {
+        ///ws_uid of the new host
+        host_ws_uid: usize,
+        ///the players that have accepted the invite, the new host is the first
+        players: Vec<Player>,
+    },
+    ///the server notifies the other members of the room that the absent player has reconnected
+    PlayerRejoined {
+        ///ws_uid of the returned player
+        ws_uid: usize,
+    },
+    ///while I was away a late member has taken over my seat in the game. I am back in the room.
+    SeatTaken {
+        ///ws_uid of the late member
+        ws_uid: usize,
+    },
+    ///the server sends it back to the sender when his msg cannot be parsed, validated or routed
+    ProtocolError {
+        ///error code
+        code: ProtocolErrorCode,
+        ///human readable reason
+        reason: String,
+        ///the type of the offending msg
+        msg_type: String,
+    },
+    ///the full state of the game in progress. The server sends it after a reconnect or a late join.
+    StateSnapshot {
+        ///all players
+        players: Vec<Player>,
+        ///vector of cards status
+        card_grid_data: Vec<Card>,
+        ///game status
+        game_status: GameStatus,
+        ///card index of first click
+        card_index_of_first_click: usize,
+        ///card index of second click
+        card_index_of_second_click: usize,
+        ///whose turn is now:  player 1,2,3,...
+        player_turn: usize,
+        ///round of the best-of series, 1 based
+        #[serde(default)]
+        round: usize,
+        ///sequence number of the last game msg. The client continues from this seq.
+        #[serde(default)]
+        seq: usize,
+    },
+    ///the state of the client differs from the checksum. The server sends the StateSnapshot.
+    StateRequest {
+        ///ws client instance unique id.
+        my_ws_uid: usize,
+    },
+    ///the client has detected a gap in the seq of the game msgs
+    ResendRequest {
+        ///ws client instance unique id.
+        my_ws_uid: usize,
+        ///the first missing seq
+        from_seq: usize,
+    },
+    ///the host creates a new room for his table
+    RoomCreate {
+        ///ws client instance unique id.
+        my_ws_uid: usize,
     },
-    ///invite
+    ///response from WebSocket server with the join code of the new room
+    RoomCreated {
+        ///short join code
+        room_code: String,
+    },
+    ///join the room with the join code
+    RoomJoin {
+        ///ws client instance unique id.
+        my_ws_uid: usize,
+        ///short join code
+        room_code: String,
+    },
+    ///response from WebSocket server when the room is joined
+    RoomJoined {
+        ///short join code
+        room_code: String,
+        ///the room has a game in progress. The new member can join it late.
+        #[serde(default)]
+        is_game_in_progress: bool,
+    },
+    ///response from WebSocket server when the join code is unknown
+    RoomNotFound {
+        ///short join code
+        room_code: String,
+    },
+    ///invite the members of the room
     Invite {
         ///ws client instance unique id. To not listen the echo to yourself.
         my_ws_uid: usize,
         ///content folder name
         asked_folder_name: String,
     },
+    ///the member says no to the invite. The host gets it.
+    InviteDecline {
+        ///ws client instance unique id.
+        my_ws_uid: usize,
+    },
+    ///the host withdraws the invite. The server sends it also when the invite expires.
+    InviteCancel {
+        ///ws_uid of the host
+        my_ws_uid: usize,
+        ///the invite has expired, nobody has cancelled it
+        #[serde(default)]
+        is_expired: bool,
+    },
+    ///the host has reordered the lobby or removed players from it.
+    ///The order of the lobby is the order of the turns, the host is always the first.
+    ///The server sends it to the members when the old host leaves the lobby with the host role.
+    LobbyChanged {
+        ///ws client instance unique id.
+        my_ws_uid: usize,
+        ///ws_uid of the players in the lobby in the order of the turns
+        lobby: Vec<usize>,
+    },
+    ///the host has removed me from the lobby
+    KickedFromLobby {
+        ///ws_uid of the host
+        host_ws_uid: usize,
+    },
     /// accept play
     PlayAccept {
         ///ws client instance unique id. To not listen the echo to yourself.
         my_ws_uid: usize,
-        ///json of vector of players
-        players: String,
+        ///vector of players
+        players: Vec<Player>,
     },
     /// player1 initialize the game data and sends it to all players
-    /// I will send json string to not confuse the server with vectors
     GameDataInit {
         ///vector of cards status
-        card_grid_data: String,
-        ///json of game_config
-        game_config: String,
-        ///json of vector of players
-        players: String,
+        card_grid_data: Vec<Card>,
+        ///game config
+        game_config: GameConfig,
+        ///vector of players
+        players: Vec<Player>,
     },
-    ///player click
+    ///player click on the first card
+    ///the client sends the click, the server applies it and sends the changes to all players
     PlayerClick1stCard {
         ///this identifies the smartphone, but not the player-in-turn
         my_ws_uid: usize,
-        ///all players
-        players: String,
-        ///vector of cards status
-        card_grid_data: String,
-        ///game status PlayerBefore1stCard or PlayerBefore2ndCard
-        game_status: GameStatus,
-        ///have to send all the state of the game
+        ///the clicked card
         card_index_of_first_click: usize,
-        ///have to send all the state of the game
-        card_index_of_second_click: usize,
+        ///the changes of the game state from the server. Empty from the client.
+        #[serde(default)]
+        events: Vec<GameEvent>,
+        ///sequence number of the game msg. The server numbers the msgs of every game.
+        #[serde(default)]
+        seq: usize,
+        ///id of the msg from its sender. The receiver ignores the duplicates.
+        #[serde(default)]
+        msg_id: usize,
+        ///checksum of the game state after this msg. A different checksum on the receiver is a desync.
+        #[serde(default)]
+        checksum: u64,
     },
-    ///player click
+    ///player click on the second card, the cards match
+    ///the client sends the click, the server applies it and sends the changes to all players
     PlayerClick2ndCard {
         ///this identifies the smartphone, but not the player-in-turn
         my_ws_uid: usize,
-        ///all players
-        players: String,
-        ///vector of cards status
-        card_grid_data: String,
-        ///game status PlayerBefore1stCard or PlayerBefore2ndCard
-        game_status: GameStatus,
-        ///have to send all the state of the game
-        card_index_of_first_click: usize,
-        ///have to send all the state of the game
+        ///the clicked card
         card_index_of_second_click: usize,
+        ///the changes of the game state from the server. Empty from the client.
+        #[serde(default)]
+        events: Vec<GameEvent>,
+        ///sequence number of the game msg. The server numbers the msgs of every game.
+        #[serde(default)]
+        seq: usize,
+        ///id of the msg from its sender. The receiver ignores the duplicates.
+        #[serde(default)]
+        msg_id: usize,
+        ///checksum of the game state after this msg. A different checksum on the receiver is a desync.
+        #[serde(default)]
+        checksum: u64,
     },
-    ///take turn begin
+    ///take turn begin, the cards don't match. Only the server sends it.
     TakeTurnBegin {
         ///this identifies the smartphone, but not the player-in-turn
         my_ws_uid: usize,
-        ///all players
-        players: String,
-        ///vector of cards status
-        card_grid_data: String,
-        ///game status PlayerBefore1stCard or PlayerBefore2ndCard
-        game_status: GameStatus,
-        ///have to send all the state of the game
-        card_index_of_first_click: usize,
-        ///have to send all the state of the game
-        card_index_of_second_click: usize,
+        ///the changes of the game state from the server. Empty from the client.
+        #[serde(default)]
+        events: Vec<GameEvent>,
+        ///sequence number of the game msg. The server numbers the msgs of every game.
+        #[serde(default)]
+        seq: usize,
+        ///id of the msg from its sender. The receiver ignores the duplicates.
+        #[serde(default)]
+        msg_id: usize,
+        ///checksum of the game state after this msg. A different checksum on the receiver is a desync.
+        #[serde(default)]
+        checksum: u64,
     },
-    ///Play Again
+    ///Play Again, all the cards are face up. Only the server sends it.
     GameOverPlayAgainBegin {
         ///this identifies the smartphone, but not the player-in-turn
         my_ws_uid: usize,
-        ///all players
-        players: String,
-        ///vector of cards status
-        card_grid_data: String,
-        ///game status PlayerBefore1stCard or PlayerBefore2ndCard
-        game_status: GameStatus,
-        ///have to send all the state of the game
-        card_index_of_first_click: usize,
-        ///have to send all the state of the game
-        card_index_of_second_click: usize,
+        ///the changes of the game state from the server. Empty from the client.
+        #[serde(default)]
+        events: Vec<GameEvent>,
+        ///sequence number of the game msg. The server numbers the msgs of every game.
+        #[serde(default)]
+        seq: usize,
+        ///id of the msg from its sender. The receiver ignores the duplicates.
+        #[serde(default)]
+        msg_id: usize,
+        ///checksum of the game state after this msg. A different checksum on the receiver is a desync.
+        #[serde(default)]
+        checksum: u64,
     },
     ///player change
+    ///the next player sends it, the server applies it and sends the changes to all players
     TakeTurnEnd {
         ///ws client instance unique id. To not listen the echo to yourself.
         my_ws_uid: usize,
-        ///all players
-        players: String,
+        ///the changes of the game state from the server. Empty from the client.
+        #[serde(default)]
+        events: Vec<GameEvent>,
+        ///sequence number of the game msg. The server numbers the msgs of every game.
+        #[serde(default)]
+        seq: usize,
+        ///id of the msg from its sender. The receiver ignores the duplicates.
+        #[serde(default)]
+        msg_id: usize,
+        ///checksum of the game state after this msg. A different checksum on the receiver is a desync.
+        #[serde(default)]
+        checksum: u64,
     },
 }
 
+impl WsMessage {
+    ///the seq of a game msg or of the snapshot, None for the other msgs
+    pub fn seq(&self) -> Option<usize> {
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match self {
+            WsMessage::PlayerClick1stCard { seq, .. }
+            | WsMessage::PlayerClick2ndCard { seq, .. }
+            | WsMessage::TakeTurnBegin { seq, .. }
+            | WsMessage::GameOverPlayAgainBegin { seq, .. }
+            | WsMessage::TakeTurnEnd { seq, .. }
+            | WsMessage::StateSnapshot { seq, .. } => Some(*seq),
+            _ => None,
+        }
+    }
+    ///the checksum of the game state in a game msg, None for the other msgs
+    pub fn checksum(&self) -> Option<u64> {
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match self {
+            WsMessage::PlayerClick1stCard { checksum, .. }
+            | WsMessage::PlayerClick2ndCard { checksum, .. }
+            | WsMessage::TakeTurnBegin { checksum, .. }
+            | WsMessage::GameOverPlayAgainBegin { checksum, .. }
+            | WsMessage::TakeTurnEnd { checksum, .. } => Some(*checksum),
+            _ => None,
+        }
+    }
+    ///the server numbers the game msgs
+    pub fn set_seq(&mut self, new_seq: usize) {
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match self {
+            WsMessage::PlayerClick1stCard { seq, .. }
+            | WsMessage::PlayerClick2ndCard { seq, .. }
+            | WsMessage::TakeTurnBegin { seq, .. }
+            | WsMessage::GameOverPlayAgainBegin { seq, .. }
+            | WsMessage::TakeTurnEnd { seq, .. }
+            | WsMessage::StateSnapshot { seq, .. } => *seq = new_seq,
+            _ => (),
+        }
+    }
+    ///the compact binary encoding for the binary WebSocket frames
+    pub fn to_binary(&self) -> Vec<u8> {
+        unwrap!(bincode::serialize(self), "error bincode serialize WsMessage")
+    }
+    ///decode the msg from a binary WebSocket frame
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, String> {
+        bincode::deserialize(bytes).map_err(|e| e.to_string())
+    }
+    ///the msg type is the name of the variant, the first key in the json object.
+    ///It tells which msg was not valid, also if the json cannot be parsed as WsMessage.
+    pub fn msg_type_of_json(json: &str) -> String {
+        serde_json::from_str::<serde_json::Value>(json)
+            .ok()
+            .and_then(|value| {
+                value
+                    .as_object()
+                    .and_then(|object| object.keys().next().cloned())
+            })
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+}
+
 ///the game can be in various statuses and that differentiate the UI and actions
 /// all players have the same game status
 #[derive(Display, AsRefStr, Serialize, Deserialize, Clone)]
@@ -190,12 +472,165 @@ pub enum GameStatus {
     Reconnect,
 }
 
+///error codes of the msg ProtocolError
+#[derive(Display, AsRefStr, Serialize, Deserialize, Clone)]
+pub enum ProtocolErrorCode {
+    ///the msg is not a valid WsMessage
+    ParseError,
+    ///the msg claims the identity of another user
+    IdentityMismatch,
+    ///the sender is not a member of a room
+    NotInRoom,
+    ///only the host of the room can send this msg
+    NotHost,
+    ///the data in the msg is not valid
+    InvalidData,
+    ///there is no game in progress in the room
+    NoGame,
+    ///the action does not respect the game rules
+    InvalidAction,
+    ///only the server sends this msg
+    UnexpectedMsg,
+}
+
 ///data for one player
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Player {
     ///ws_uid
     pub ws_uid: usize,
     ///field for src attribute for HTML element image and filename of card image
     pub points: usize,
+    ///the player has lost the connection. The turns skip him.
+    #[serde(default)]
+    pub is_absent: bool,
+    ///points of the finished rounds of the series
+    #[serde(default)]
+    pub total_points: usize,
+    ///rounds won in the series
+    #[serde(default)]
+    pub rounds_won: usize,
+    ///statistics of this round for the summary at the game over
+    #[serde(default)]
+    pub stats: PlayerStats,
+    ///display name chosen by the player, empty for the default name
+    #[serde(default)]
+    pub name: String,
+    ///css color of the player for the scoreboard and the grid background
+    #[serde(default)]
+    pub color: String,
+    ///img src of the avatar, a card image from the content pack
+    #[serde(default)]
+    pub avatar: String,
+}
+
+impl Player {
+    ///new player with the default name, color and avatar
+    pub fn new(ws_uid: usize) -> Self {
+        Player {
+            ws_uid,
+            points: 0,
+            is_absent: false,
+            total_points: 0,
+            rounds_won: 0,
+            stats: PlayerStats::default(),
+            name: String::new(),
+            color: String::new(),
+            avatar: String::new(),
+        }
+    }
+    ///the name for the UI, Player1, Player2,... if the player has not chosen a name
+    pub fn display_name(&self, player_number: usize) -> String {
+        if self.name == "" {
+            format!("Player{}", player_number)
+        } else {
+            self.name.clone()
+        }
+    }
+    ///copy the name, color and avatar of the profile
+    pub fn set_profile(&mut self, profile: &Player) {
+        self.name = profile.name.clone();
+        self.color = profile.color.clone();
+        self.avatar = profile.avatar.clone();
+    }
+    ///the name is cut to the max length. A color not in PLAYER_COLORS
+    ///and an avatar not in the avatars of the content drop back to the defaults.
+    pub fn drop_invalid_profile(&mut self, avatars: &[String]) {
+        if self.name.chars().count() > PLAYER_NAME_MAX_CHARS {
+            self.name = self.name.chars().take(PLAYER_NAME_MAX_CHARS).collect();
+        }
+        if !PLAYER_COLORS.contains(&self.color.as_str()) {
+            self.color = String::new();
+        }
+        if !avatars.contains(&self.avatar) {
+            self.avatar = String::new();
+        }
+    }
+}
+
+///statistics of one player recorded during the round
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct PlayerStats {
+    ///the second card did not match
+    pub mismatches: usize,
+    ///pairs found one after another in the current turn
+    pub streak: usize,
+    ///the longest streak of this round
+    pub longest_streak: usize,
+    ///seconds of play in his turns. Only the server measures the time.
+    pub seconds: u64,
+}
+
+///game config
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GameConfig {
+    ///card moniker - the text/name of the card
+    ///the zero element is card face down or empty, example alphabet begins with index 01 : A
+    pub card_moniker: Vec<String>,
+    ///img filenames
+    pub img_filename: Vec<String>,
+    ///sound filenames
+    pub sound_filename: Vec<String>,
+    ///card image width
+    pub card_width: usize,
+    ///card image height
+    pub card_height: usize,
+    ///number of cards horizontally
+    pub grid_items_hor: usize,
+    ///number of card vertically
+    pub grid_items_ver: usize,
+}
+
+impl GameConfig {
+    ///the img src of the card images of the content folder. The player chooses his avatar from them.
+    pub fn avatars(&self, asked_folder_name: &str) -> Vec<String> {
+        self.img_filename
+            .iter()
+            .skip(1)
+            .map(|img_filename| format!("content/{}/img/{}", asked_folder_name, img_filename))
+            .collect()
+    }
+}
+
+///the 3 possible statuses of one card
+#[derive(Serialize, Deserialize, AsRefStr, Clone)]
+pub enum CardStatusCardFace {
+    ///card face down
+    Down,
+    ///card face Up Temporary
+    UpTemporary,
+    ///card face up Permanently
+    UpPermanently,
+}
+
+///all the data for one card
+///the server keeps the authoritative copy, the clients only render it
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Card {
+    ///card status
+    pub status: CardStatusCardFace,
+    ///field for src attribute for HTML element imagea and filename of card image
+    pub card_number_and_img_src: usize,
+    ///field for id attribute for HTML element image contains the card index
+    pub card_index_and_id: usize,
 }
 //endregion
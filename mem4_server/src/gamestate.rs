@@ -0,0 +1,103 @@
+//! gamestate.rs - the authoritative game state is kept on the server.
+//! The clients send only their clicks. The server applies them with the game engine from mem4_common
+//! and sends only the changes of the state as events to all players. A client cannot corrupt the game anymore.
+//! The whole state goes only in GameDataInit and in the StateSnapshot.
+//! Only the server measures the play time of the players for the summary at the game over.
+//! The seq of the msg is set when the room sends it. The checksum lets the clients detect a desync.
+
+//region: use
+use mem4_common::{transition, GameAction, GameEvent, GameState, GameStatus, WsMessage};
+use std::time::Instant;
+//endregion
+
+///apply the action of the player and return the msg with the changes for all players.
+///An action that does not respect the game rules returns an error and the state is not changed.
+///The time since the last action goes to the play time of the player in turn.
+#[allow(clippy::wildcard_enum_match_arm)]
+pub fn apply_action(
+    game: &mut GameState,
+    ws_uid: usize,
+    action: &GameAction,
+    turn_clock: &mut Instant,
+) -> Result<WsMessage, String> {
+    let (mut new_state, mut events) = transition(game, action)?;
+    //waiting for the take turn is not play time
+    if game.game_status.as_ref() != GameStatus::TakeTurnBegin.as_ref() {
+        let player_number = game.player_turn;
+        if let Some(player) = player_number
+            .checked_sub(1)
+            .and_then(|index| new_state.players.get_mut(index))
+        {
+            player.stats.seconds = unwrap!(player
+                .stats
+                .seconds
+                .checked_add(turn_clock.elapsed().as_secs()));
+            events.push(GameEvent::StatsChanged {
+                player_number,
+                stats: player.stats.clone(),
+            });
+        }
+    }
+    *turn_clock = Instant::now();
+    *game = new_state;
+
+    let my_ws_uid = ws_uid;
+    let checksum = game.checksum();
+    //the msg tells the outcome of the action, the events only the changes
+    Ok(match action {
+        GameAction::Click1stCard { card_index, .. } => WsMessage::PlayerClick1stCard {
+            my_ws_uid,
+            card_index_of_first_click: *card_index,
+            events,
+            seq: 0,
+            msg_id: 0,
+            checksum,
+        },
+        GameAction::Click2ndCard { card_index, .. } => match game.game_status {
+            GameStatus::TakeTurnBegin => WsMessage::TakeTurnBegin {
+                my_ws_uid,
+                events,
+                seq: 0,
+                msg_id: 0,
+                checksum,
+            },
+            GameStatus::GameOverPlayAgainBegin => WsMessage::GameOverPlayAgainBegin {
+                my_ws_uid,
+                events,
+                seq: 0,
+                msg_id: 0,
+                checksum,
+            },
+            _ => WsMessage::PlayerClick2ndCard {
+                my_ws_uid,
+                card_index_of_second_click: *card_index,
+                events,
+                seq: 0,
+                msg_id: 0,
+                checksum,
+            },
+        },
+        GameAction::TakeTurnEnd { .. } | GameAction::SkipTurn => WsMessage::TakeTurnEnd {
+            my_ws_uid,
+            events,
+            seq: 0,
+            msg_id: 0,
+            checksum,
+        },
+    })
+}
+
+///the latest full snapshot of the game for a reconnected player.
+///The seq is the seq of the last game msg. The player continues from it.
+pub fn snapshot(game: &GameState, seq: usize) -> WsMessage {
+    WsMessage::StateSnapshot {
+        players: game.players.clone(),
+        card_grid_data: game.card_grid_data.clone(),
+        game_status: game.game_status.clone(),
+        card_index_of_first_click: game.card_index_of_first_click,
+        card_index_of_second_click: game.card_index_of_second_click,
+        player_turn: game.player_turn,
+        round: game.round,
+        seq,
+    }
+}
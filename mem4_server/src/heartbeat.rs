@@ -0,0 +1,39 @@
+//! heartbeat.rs - the server pings periodically all the connections.
+//! Phones that go to sleep or lose Wi-Fi often leave half-open sockets.
+//! A connection without a Pong in the pong timeout is declared dead and removed.
+//! The turn of an absent player is skipped after the grace period.
+//! An invite expires after the invite timeout.
+
+//region: use
+use crate::Rooms;
+
+use std::thread;
+use std::time::Duration;
+//endregion
+
+//region: const
+///seconds between two pings
+const PING_INTERVAL_SECONDS: u64 = 10;
+//endregion
+
+///start the background thread that pings the users, reaps the dead connections,
+///skips the turns of the absent players and expires the invites
+pub fn start_heartbeat(
+    rooms: Rooms,
+    pong_timeout: Duration,
+    absent_grace: Duration,
+    invite_timeout: Duration,
+) {
+    thread::spawn(move || {
+        let mut ping_id: usize = 0;
+        loop {
+            thread::sleep(Duration::from_secs(PING_INTERVAL_SECONDS));
+            ping_id = ping_id.wrapping_add(1);
+            let mut rooms_and_users = rooms.lock().expect("error rooms.lock()");
+            rooms_and_users.reap_dead_connections(pong_timeout);
+            rooms_and_users.skip_absent_turns(absent_grace);
+            rooms_and_users.expire_invites(invite_timeout);
+            rooms_and_users.ping_all(ping_id);
+        }
+    });
+}
@@ -9,7 +9,46 @@
 //! Using Warp for a simple memory game for kids - mem4.  
 //! On the local public IP address on port 80 listens to http and WebSocket.  
 //! Route for http `/` serves static files from folder `/mem4/`  
-//! Route `/mem4ws/` broadcast all WebSocket msg to all connected clients except sender  
+//! Route `/mem4ws/` sends the WebSocket msg only to the other members of the same room  
+//! A host creates a room and gets a short join code. The other players join the room with this code.  
+//! The server keeps the authoritative game state. It applies the clicks of the players and sends the result to all of them.  
+//! The server issues the ws_uid and a session token. Only a client with the session token can reconnect to the same ws_uid.  
+//! The server knows the real sender of every msg. A msg with the my_ws_uid of another user is rejected and logged.  
+//! The msgs are routed only by the server record of the room members, never by the data in the msg.  
+//! After a reconnect with a valid session the player gets the StateSnapshot of the game he missed.  
+//! A msg that cannot be parsed, validated or routed is answered with a ProtocolError to the sender.  
+//! The first msg of a connection is the Hello with the protocol version. A too old client gets HelloRejected  
+//! and the server closes the connection. A newer client is downgraded to the server version.  
+//! Only the known capabilities are accepted.  
+//! The server pings all connections after their Hello. A connection without Pong in the pong timeout  
+//! (`--pong_timeout`, default 30 seconds) is dead and the other members get PlayerLeft.  
+//! The turns skip the absent players. If the absent player is in turn, his turn is skipped  
+//! after the grace period (`--absent_grace`, default 30 seconds). On reconnect the others get PlayerRejoined.  
+//! If the host leaves, the host role passes to the next connected member with the msg HostChanged.  
+//! The new host gets the lobby of the players that have accepted the invite and can start the game.  
+//! The old host leaves the lobby and all the members get LobbyChanged.  
+//! A late member can join the game in progress with JoinInProgress. He takes over the slot of an absent player  
+//! or gets a new slice of cards and the last place in the turns. All the players get the new StateSnapshot.  
+//! The player with the seat taken gets SeatTaken after the Hello of his reconnect.  
+//! After the game over the host can invite the room again for a rematch. The rounds make a best-of series:  
+//! the server keeps the total points and the rounds won and rotates the starting player.  
+//! An Invite during the game in progress is rejected with a ProtocolError, the game goes on.  
+//! The server deals the cards of GameDataInit itself, the card grid sent by the host is ignored.  
+//! The players of GameDataInit must be in the lobby and every player only once.  
+//! The server remembers the name, color and avatar of every player from PlayAccept, JoinInProgress and GameDataInit  
+//! for the lobby of a new host and for the late players.  
+//! The color must be one of the player colors and the avatar a card image of the invited content,  
+//! else they drop back to the defaults. A too long name is cut.  
+//! The host changes the order of the lobby or removes players with LobbyChanged. The removed players get KickedFromLobby.  
+//! A member declines the invite with InviteDecline, the host withdraws it with InviteCancel. An invite without the start  
+//! of the game expires after the invite timeout (`--invite_timeout`, default 120 seconds) and all the members get InviteCancel.  
+//! The server numbers the game msgs of every game with the seq. A game msg with an already seen msg_id is ignored.  
+//! A client with a gap in the seq sends ResendRequest and gets the missed msgs again or the StateSnapshot.  
+//! Every game msg has the checksum of the game state. A client with a desync sends StateRequest and gets the StateSnapshot.  
+//! The game msgs bring only the changes of the state as events. The whole card grid goes only in GameDataInit and StateSnapshot.  
+//! A client with the capability bincode in the Hello gets its msgs as bincode in binary frames, the others get json.  
+//! The server reads both, the binary and the text frames.  
+//! A msg for many receivers is serialized only once for every wire format.  
 //! 
 //! ## Google vm
 //! One working server is installed on google vm.  
@@ -50,6 +89,13 @@
 )]
 //endregion
 
+//region: mod is used only in main file. All the rest use use crate
+mod gamestate;
+mod heartbeat;
+mod rooms;
+mod wiremsg;
+//endregion
+
 //region: extern and use statements
 extern crate ansi_term;
 extern crate clap;
@@ -58,6 +104,7 @@ extern crate futures;
 #[macro_use]
 extern crate log;
 extern crate mem4_common;
+extern crate rand;
 extern crate regex;
 extern crate serde;
 extern crate serde_derive;
@@ -66,27 +113,33 @@ extern crate warp;
 #[macro_use]
 extern crate unwrap;
 
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches};
 use env_logger::Env;
 use futures::sync::mpsc;
 use futures::{Future, Stream};
-use mem4_common::{Player, WsMessage};
+use crate::rooms::{Room, RoomsAndUsers};
+use crate::wiremsg::WireMsg;
+use mem4_common::{
+    build_deck, GameAction, GameConfig, GameState, GameStatus, Player, ProtocolErrorCode, WsMessage,
+    CAPABILITY_BINARY, CAPABILITY_SNAPSHOT, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
 use regex::Regex;
-use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::process::Command;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use warp::ws::{Message, WebSocket};
 use warp::Filter;
 //endregion
 
 //region: enum, structs, const,...
-/// Our status of currently connected users.
-/// - Key is their id
-/// - Value is a sender of `warp::ws::Message`
-type Users = Arc<Mutex<HashMap<usize, mpsc::UnboundedSender<Message>>>>;
+/// Our status of currently connected users and the rooms they are members of.
+/// Every room has its own game state.
+type Rooms = Arc<Mutex<RoomsAndUsers>>;
 
+///the folder of the static files, also of the content
+const WEB_FOLDER: &str = "./mem4/";
 //endregion
 
 ///main function of the binary
@@ -137,6 +190,27 @@ fn main() {
                 .default_value("8084")
                 .help("port for listening"),
         )
+        .arg(
+            Arg::with_name("prm_pong_timeout")
+                .long("pong_timeout")
+                .value_name("seconds")
+                .default_value("30")
+                .help("a connection without pong in this time is dead"),
+        )
+        .arg(
+            Arg::with_name("prm_absent_grace")
+                .long("absent_grace")
+                .value_name("seconds")
+                .default_value("30")
+                .help("the turn of an absent player is skipped after this time"),
+        )
+        .arg(
+            Arg::with_name("prm_invite_timeout")
+                .long("invite_timeout")
+                .value_name("seconds")
+                .default_value("120")
+                .help("an invite without the start of the game expires after this time"),
+        )
         .get_matches();
 
     //from string parameters to strong types
@@ -163,6 +237,9 @@ fn main() {
     let local_ip = IpAddr::V4(fnl_prm_ip.parse::<Ipv4Addr>().expect("not an ip address"));
     let local_port = u16::from_str_radix(&fnl_prm_port, 10).expect("not a number");
     let local_addr = SocketAddr::new(local_ip, local_port);
+    let pong_timeout = duration_of_prm(&matches, "prm_pong_timeout");
+    let absent_grace = duration_of_prm(&matches, "prm_absent_grace");
+    let invite_timeout = duration_of_prm(&matches, "prm_invite_timeout");
 
     info!(
         "mem4 http server listening on {} and WebSocket on /mem4ws/",
@@ -170,66 +247,67 @@ fn main() {
     );
     //endregion
 
-    // Keep track of all connected users, key is usize, value
-    // is a WebSocket sender.
-    let users = Arc::new(Mutex::new(HashMap::new()));
+    // Keep track of all connected users and rooms.
+    // A user is a WebSocket sender, a room has members and a game.
+    let rooms = Arc::new(Mutex::new(RoomsAndUsers::default()));
+    // Ping all the users, remove the dead connections, skip the turns of the absent players
+    // and expire the old invites.
+    heartbeat::start_heartbeat(
+        Arc::<Mutex<RoomsAndUsers>>::clone(&rooms),
+        pong_timeout,
+        absent_grace,
+        invite_timeout,
+    );
     // Turn our "state" into a new Filter...
-    //let users = warp::any().map(move || users.clone());
-    //Clippy recommends this craziness instead of just users.clone()
-    let users = warp::any().map(move || {
-        Arc::<
-            std::sync::Mutex<
-                std::collections::HashMap<
-                    usize,
-                    futures::sync::mpsc::UnboundedSender<warp::ws::Message>,
-                >,
-            >,
-        >::clone(&users)
-    });
+    //let rooms = warp::any().map(move || rooms.clone());
+    //Clippy recommends this craziness instead of just rooms.clone()
+    let rooms = warp::any().map(move || Arc::<Mutex<RoomsAndUsers>>::clone(&rooms));
 
     //WebSocket server
     // GET from route /mem4ws/ -> WebSocket upgrade
     let websocket = warp::path("mem4ws")
         // The `ws2()` filter will prepare WebSocket handshake...
         .and(warp::ws2())
-        .and(users)
+        .and(rooms)
         // Match `/mem4ws/url_param` it can be any string.
         .and(warp::path::param::<String>())
-        .map(|ws: warp::ws::Ws2, users, url_param| {
+        .map(|ws: warp::ws::Ws2, rooms, url_param| {
             // This will call our function if the handshake succeeds.
-            ws.on_upgrade(move |socket| user_connected(socket, users, url_param))
+            ws.on_upgrade(move |socket| user_connected(socket, rooms, url_param))
         });
 
     //static file server
     // GET files of route / -> are from folder /mem4/
-    let fileserver = warp::fs::dir("./mem4/");
+    let fileserver = warp::fs::dir(WEB_FOLDER);
 
     let routes = fileserver.or(websocket);
     warp::serve(routes).run(local_addr);
 }
 
+///the cmdline parameter in seconds
+fn duration_of_prm(matches: &ArgMatches, prm_name: &str) -> Duration {
+    Duration::from_secs(
+        matches
+            .value_of(prm_name)
+            .expect("error on cmdline parameter")
+            .parse::<u64>()
+            .expect("the cmdline parameter is not a number"),
+    )
+}
+
 //the url_param is not consumed in this function and Clippy wants a reference instead a value
 #[allow(clippy::needless_pass_by_value)]
 //region: WebSocket callbacks: connect, msg, disconnect
 ///new user connects
 fn user_connected(
     ws: WebSocket,
-    users: Users,
+    rooms: Rooms,
     url_param: String,
 ) -> impl Future<Item = (), Error = ()> {
-    //the client sends his ws_uid in url_param. it is a random number.
-    info!("user_connect() url_param: {}", url_param);
-    //convert string to usize
-    //hahahahaha syntax 'turbofish' ::<>
-    let my_id = unwrap!(url_param.parse::<usize>());
-    //if uid already exists, it is an error
-    for (&uid, ..) in users.lock().expect("error users.lock()").iter() {
-        if uid == my_id {
-            //disconnect the old user
-            info!("user_disconnected for reconnect: {}", my_id);
-            user_disconnected(my_id, &users);
-        }
-    }
+    //the client sends his session token in url_param for reconnect.
+    //For the first connection the url_param is anything else like "new".
+    //the token is a secret and it is not written in the log
+    info!("user_connect() url_param length: {}", url_param.len());
 
     // Split the socket into a sender and receive of messages.
     let (user_ws_tx, user_ws_rx) = ws.split();
@@ -245,33 +323,30 @@ fn user_connected(
     );
 
     // Save the sender in our list of connected users.
-    info!("users.insert: {}", my_id);
-    users.lock().expect("error uses.lock()").insert(my_id, tx);
+    // The server issues the ws_uid. A valid session token gets back the old ws_uid.
+    let (my_id, connection_id) = rooms
+        .lock()
+        .expect("error rooms.lock()")
+        .connect_user(&url_param, tx);
+    info!("users.insert: {} connection: {}", my_id, connection_id);
 
     // Return a `Future` that is basically a state machine managing
     // this specific user's connection.
     // Make an extra clone to give to our disconnection handler...
-    //let users2 = users.clone();
-    //Clippy recommends this craziness instead of users.clone()
-    let users2 = Arc::<
-        std::sync::Mutex<
-            std::collections::HashMap<
-                usize,
-                futures::sync::mpsc::UnboundedSender<warp::ws::Message>,
-            >,
-        >,
-    >::clone(&users);
+    //let rooms2 = rooms.clone();
+    //Clippy recommends this craziness instead of rooms.clone()
+    let rooms2 = Arc::<Mutex<RoomsAndUsers>>::clone(&rooms);
 
     user_ws_rx
         // Every time the user sends a message, call receive message
         .for_each(move |msg| {
-            receive_message(my_id, &msg, &users);
+            receive_message(my_id, &msg, &rooms);
             Ok(())
         })
         // for_each will keep processing as long as the user stays
         // connected. Once they disconnect, then...
         .then(move |result| {
-            user_disconnected(my_id, &users2);
+            user_disconnected(my_id, connection_id, &rooms2);
             result
         })
         // If at any time, there was a WebSocket error, log here...
@@ -281,43 +356,28 @@ fn user_connected(
 }
 
 ///on receive WebSocket message
-fn receive_message(ws_uid_of_message: usize, messg: &Message, users: &Users) {
-    // Skip any non-Text messages...
-    let msg = if let Ok(s) = messg.to_str() {
-        s
-    } else {
-        return;
-    };
-
-    let new_msg = msg.to_string();
-    //info!("msg: {}", new_msg);
-
+fn receive_message(ws_uid_of_message: usize, messg: &Message, rooms: &Rooms) {
     //There are different messages coming from the mem4 wasm app
-    //Invite must be broadcasted to all users
-    //all others must be forwarded to exactly the other player.
+    //RoomCreate and RoomJoin manage the members of the rooms.
+    //Invite, PlayAccept and GameDataInit are sent only to the other members of the room.
+    //The clicks are applied to the authoritative game state and the result is sent to all players.
 
-    let msg: WsMessage = serde_json::from_str(&new_msg).unwrap_or_else(|_x| WsMessage::Dummy {
-        dummy: String::from("error"),
-    });
+    let wire_msg = match parse_message(ws_uid_of_message, messg, rooms) {
+        Some(wire_msg) => wire_msg,
+        None => return,
+    };
+    let msg = wire_msg.msg();
+    if !is_msg_allowed(ws_uid_of_message, msg, rooms) {
+        return;
+    }
+    let msg_type = msg.as_ref().to_string();
 
     match msg {
         WsMessage::Dummy { dummy } => info!("Dummy: {}", dummy),
-        WsMessage::RequestWsUid { test } => {
-            info!("RequestWsUid: {}", test);
-            let j = serde_json::to_string(&WsMessage::ResponseWsUid { your_ws_uid: ws_uid_of_message })
-                .expect("serde_json::to_string(&WsMessage::ResponseWsUid { your_ws_uid: ws_uid_of_message })");
-            info!("send ResponseWsUid: {}", j);
-            match users
-                .lock()
-                .expect("error users.lock()")
-                .get(&ws_uid_of_message)
-                .unwrap()
-                .unbounded_send(Message::text(j))
-            {
-                Ok(()) => (),
-                Err(_disconnected) => {}
-            }
-        },
+        WsMessage::Hello {
+            protocol_version,
+            capabilities,
+        } => hello(rooms, ws_uid_of_message, *protocol_version, capabilities),
         /* obsolete, but keep it as an example how to return a text file over websocket
         WsMessage::RequestGameConfig { filename } => {
             info!("RequestGameConfig: {}", filename);
@@ -350,79 +410,664 @@ fn receive_message(ws_uid_of_message: usize, messg: &Message, users: &Users) {
             }
         }
         */
-        WsMessage::Invite { .. } => broadcast(users, ws_uid_of_message, &new_msg),
-        WsMessage::ResponseWsUid { .. } => info!("ResponseWsUid: {}", ""),
-        WsMessage::PlayAccept { players, .. }
-        | WsMessage::PlayerClick1stCard { players, .. }
-        | WsMessage::PlayerClick2ndCard { players, .. }
-        | WsMessage::GameDataInit { players, .. }
-        | WsMessage::TakeTurnBegin { players, .. }
-        | WsMessage::TakeTurnEnd { players, .. }
-        | WsMessage::GameOverPlayAgainBegin { players, .. } => {
-            send_to_other_players(users, ws_uid_of_message, &new_msg, &players)
+        WsMessage::Pong { .. } => rooms
+            .lock()
+            .expect("error rooms.lock()")
+            .pong(ws_uid_of_message),
+        WsMessage::RoomCreate { .. } => room_create(rooms, ws_uid_of_message),
+        WsMessage::RoomJoin { room_code, .. } => room_join(rooms, ws_uid_of_message, room_code),
+        WsMessage::Invite {
+            asked_folder_name, ..
+        } => invite(rooms, ws_uid_of_message, asked_folder_name, &wire_msg),
+        WsMessage::JoinInProgress { players, .. } => {
+            join_in_progress(rooms, ws_uid_of_message, players, &msg_type)
+        }
+        WsMessage::InviteDecline { .. } => {
+            decline_invite(rooms, ws_uid_of_message, &wire_msg, &msg_type)
+        }
+        WsMessage::InviteCancel { .. } => {
+            let mut rooms_and_users = rooms.lock().expect("error rooms.lock()");
+            if let Err((code, reason)) = rooms_and_users.cancel_invite(ws_uid_of_message) {
+                rooms_and_users.send_protocol_error(ws_uid_of_message, code, reason, &msg_type);
+            }
+        }
+        WsMessage::LobbyChanged { lobby, .. } => {
+            change_lobby(rooms, ws_uid_of_message, lobby, &msg_type)
+        }
+        WsMessage::ResendRequest { from_seq, .. } => {
+            let rooms_and_users = rooms.lock().expect("error rooms.lock()");
+            rooms_and_users.resend_game_msgs(ws_uid_of_message, *from_seq);
+        }
+        WsMessage::StateRequest { .. } => state_request(rooms, ws_uid_of_message),
+        //only the server sends these msgs.
+        //Also only the server decides the outcome of the second click.
+        WsMessage::ResponseWsUid { .. }
+        | WsMessage::HelloRejected { .. }
+        | WsMessage::Ping { .. }
+        | WsMessage::PlayerLeft { .. }
+        | WsMessage::HostChanged { .. }
+        | WsMessage::JoinedInProgress { .. }
+        | WsMessage::PlayerRejoined { .. }
+        | WsMessage::SeatTaken { .. }
+        | WsMessage::KickedFromLobby { .. }
+        | WsMessage::ProtocolError { .. }
+        | WsMessage::StateSnapshot { .. }
+        | WsMessage::RoomCreated { .. }
+        | WsMessage::RoomJoined { .. }
+        | WsMessage::RoomNotFound { .. }
+        | WsMessage::TakeTurnBegin { .. }
+        | WsMessage::GameOverPlayAgainBegin { .. } => {
+            server_only_msg(rooms, ws_uid_of_message, &msg_type)
+        }
+        WsMessage::PlayAccept { players, .. } => {
+            play_accept(rooms, ws_uid_of_message, players, &msg_type)
+        }
+        WsMessage::GameDataInit {
+            game_config,
+            players,
+            ..
+        } => game_data_init(rooms, ws_uid_of_message, game_config.clone(), players.clone()),
+        WsMessage::PlayerClick1stCard {
+            card_index_of_first_click,
+            msg_id,
+            ..
+        } => apply_to_game(rooms, ws_uid_of_message, &msg_type, *msg_id, |player_number| {
+            GameAction::Click1stCard {
+                player_number,
+                card_index: *card_index_of_first_click,
+            }
+        }),
+        WsMessage::PlayerClick2ndCard {
+            card_index_of_second_click,
+            msg_id,
+            ..
+        } => apply_to_game(rooms, ws_uid_of_message, &msg_type, *msg_id, |player_number| {
+            GameAction::Click2ndCard {
+                player_number,
+                card_index: *card_index_of_second_click,
+            }
+        }),
+        WsMessage::TakeTurnEnd { msg_id, .. } => {
+            apply_to_game(rooms, ws_uid_of_message, &msg_type, *msg_id, |player_number| {
+                GameAction::TakeTurnEnd { player_number }
+            })
         }
     }
 }
 
-///New message from this user send to all other players except sender.
-fn send_to_other_players(
-    users: &Users,
-    ws_uid_of_message: usize,
-    new_msg: &str,
-    string_players: &str,
-) {
-    //info!("send_to_other_players: {}", new_msg);
+///the client has a desync. He gets the StateSnapshot.
+fn state_request(rooms: &Rooms, ws_uid_of_message: usize) {
+    info!("desync reported by: {}", ws_uid_of_message);
+    let rooms_and_users = rooms.lock().expect("error rooms.lock()");
+    rooms_and_users.send_state_snapshot(ws_uid_of_message);
+}
 
-    let players: Vec<Player> =
-        serde_json::from_str(string_players).expect("error serde_json::from_str(string_players)");
+///only the server sends this msg. The client gets the ProtocolError.
+fn server_only_msg(rooms: &Rooms, ws_uid_of_message: usize, msg_type: &str) {
+    rooms.lock().expect("error rooms.lock()").send_protocol_error(
+        ws_uid_of_message,
+        ProtocolErrorCode::UnexpectedMsg,
+        "only the server sends this msg",
+        msg_type,
+    );
+}
 
-    for (&uid, tx) in users.lock().expect("error users.lock()").iter() {
-        let mut is_player;
-        is_player = false;
-        for pl in &players {
-            if pl.ws_uid == uid {
-                is_player = true;
+///the msg of the frame. A binary frame is bincode, a text frame is json. The msg keeps its frame for the forwarding.
+///Other frames are skipped. Malformed data fails here in one place and the sender gets the ProtocolError.
+fn parse_message(ws_uid_of_message: usize, messg: &Message, rooms: &Rooms) -> Option<WireMsg> {
+    if messg.is_binary() {
+        match WsMessage::from_binary(messg.as_bytes()) {
+            Ok(msg) => Some(WireMsg::from_binary(msg, messg.as_bytes().to_vec())),
+            Err(e) => {
+                rooms.lock().expect("error rooms.lock()").send_protocol_error(
+                    ws_uid_of_message,
+                    ProtocolErrorCode::ParseError,
+                    &e,
+                    "binary",
+                );
+                None
             }
         }
-        if ws_uid_of_message != uid && is_player {
-            match tx.unbounded_send(Message::text(String::from(new_msg))) {
-                Ok(()) => (),
-                Err(_disconnected) => {
-                    // The tx is disconnected, our `user_disconnected` code
-                    // should be happening in another task, nothing more to
-                    // do here.
+    } else if let Ok(text) = messg.to_str() {
+        //info!("msg: {}", text);
+        match serde_json::from_str(text) {
+            Ok(msg) => Some(WireMsg::from_json(msg, text.to_string())),
+            Err(e) => {
+                rooms.lock().expect("error rooms.lock()").send_protocol_error(
+                    ws_uid_of_message,
+                    ProtocolErrorCode::ParseError,
+                    &e.to_string(),
+                    &WsMessage::msg_type_of_json(text),
+                );
+                None
+            }
+        }
+    } else {
+        None
+    }
+}
+
+///the checks of the sender before the msg is processed. A rejected msg gets the ProtocolError.
+fn is_msg_allowed(ws_uid_of_message: usize, msg: &WsMessage, rooms: &Rooms) -> bool {
+    //the server knows the real sender. A msg that claims another identity is rejected.
+    if let Some(claimed_ws_uid) = claimed_ws_uid(msg) {
+        if claimed_ws_uid != ws_uid_of_message {
+            rooms.lock().expect("error rooms.lock()").send_protocol_error(
+                ws_uid_of_message,
+                ProtocolErrorCode::IdentityMismatch,
+                &format!(
+                    "ws_uid {} claims to be ws_uid {}",
+                    ws_uid_of_message, claimed_ws_uid
+                ),
+                msg.as_ref(),
+            );
+            return false;
+        }
+    }
+
+    let msg_type = msg.as_ref();
+    //the first msg must be the Hello. The Pong can come before it on a reconnect.
+    if msg_type != "Hello"
+        && msg_type != "Pong"
+        && rooms
+            .lock()
+            .expect("error rooms.lock()")
+            .protocol_version_of_user(ws_uid_of_message)
+            == 0
+    {
+        rooms.lock().expect("error rooms.lock()").send_protocol_error(
+            ws_uid_of_message,
+            ProtocolErrorCode::UnexpectedMsg,
+            "the first msg must be Hello",
+            msg_type,
+        );
+        return false;
+    }
+    true
+}
+
+///the identity that the client writes in the msg. It must be the same as the socket.
+fn claimed_ws_uid(msg: &WsMessage) -> Option<usize> {
+    match msg {
+        WsMessage::RoomCreate { my_ws_uid }
+        | WsMessage::RoomJoin { my_ws_uid, .. }
+        | WsMessage::Invite { my_ws_uid, .. }
+        | WsMessage::PlayAccept { my_ws_uid, .. }
+        | WsMessage::InviteDecline { my_ws_uid }
+        | WsMessage::InviteCancel { my_ws_uid, .. }
+        | WsMessage::LobbyChanged { my_ws_uid, .. }
+        | WsMessage::JoinInProgress { my_ws_uid, .. }
+        | WsMessage::ResendRequest { my_ws_uid, .. }
+        | WsMessage::StateRequest { my_ws_uid }
+        | WsMessage::PlayerClick1stCard { my_ws_uid, .. }
+        | WsMessage::PlayerClick2ndCard { my_ws_uid, .. }
+        | WsMessage::TakeTurnBegin { my_ws_uid, .. }
+        | WsMessage::GameOverPlayAgainBegin { my_ws_uid, .. }
+        | WsMessage::TakeTurnEnd { my_ws_uid, .. } => Some(*my_ws_uid),
+        WsMessage::Dummy { .. }
+        | WsMessage::Hello { .. }
+        | WsMessage::ResponseWsUid { .. }
+        | WsMessage::HelloRejected { .. }
+        | WsMessage::Ping { .. }
+        | WsMessage::Pong { .. }
+        | WsMessage::PlayerLeft { .. }
+        | WsMessage::HostChanged { .. }
+        | WsMessage::JoinedInProgress { .. }
+        | WsMessage::PlayerRejoined { .. }
+        | WsMessage::SeatTaken { .. }
+        | WsMessage::KickedFromLobby { .. }
+        | WsMessage::ProtocolError { .. }
+        | WsMessage::StateSnapshot { .. }
+        | WsMessage::RoomCreated { .. }
+        | WsMessage::RoomJoined { .. }
+        | WsMessage::RoomNotFound { .. }
+        | WsMessage::GameDataInit { .. } => None,
+    }
+}
+
+///the handshake. A too old client is rejected. A newer client is downgraded to the server version.
+///The answer has the ws_uid and session token. After a reconnect the player gets the state he missed.
+fn hello(rooms: &Rooms, ws_uid_of_message: usize, protocol_version: usize, capabilities: &[String]) {
+    let mut rooms_and_users = rooms.lock().expect("error rooms.lock()");
+    if protocol_version < MIN_PROTOCOL_VERSION {
+        info!(
+            "HelloRejected ws_uid {} protocol_version {}",
+            ws_uid_of_message, protocol_version
+        );
+        let wire_msg = WireMsg::new(WsMessage::HelloRejected {
+            protocol_version: PROTOCOL_VERSION,
+            reason: format!(
+                "The server accepts protocol versions from {} to {}.",
+                MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            ),
+        });
+        rooms_and_users.send_to_user(ws_uid_of_message, &wire_msg);
+        //the rejected client cannot talk with this server
+        rooms_and_users.close_connection(ws_uid_of_message);
+        return;
+    }
+    let agreed_version = std::cmp::min(protocol_version, PROTOCOL_VERSION);
+    //only the capabilities known to the server
+    let known_capabilities: Vec<String> = capabilities
+        .iter()
+        .filter(|c| c.as_str() == CAPABILITY_SNAPSHOT || c.as_str() == CAPABILITY_BINARY)
+        .cloned()
+        .collect();
+    if let Some(user) = rooms_and_users.users.get_mut(&ws_uid_of_message) {
+        user.protocol_version = agreed_version;
+        user.capabilities = known_capabilities.clone();
+    }
+    let response = WireMsg::new(WsMessage::ResponseWsUid {
+        your_ws_uid: ws_uid_of_message,
+        session_token: rooms_and_users.session_token_of_user(ws_uid_of_message),
+        protocol_version: agreed_version,
+        capabilities: known_capabilities,
+    });
+    info!(
+        "send ResponseWsUid to: {} protocol_version: {}",
+        ws_uid_of_message, agreed_version
+    );
+    rooms_and_users.send_to_user(ws_uid_of_message, &response);
+    //after a reconnect the player gets the state he missed
+    if rooms_and_users.has_capability(ws_uid_of_message, CAPABILITY_SNAPSHOT) {
+        if let Some((game, game_seq)) = rooms_and_users
+            .room_of_user(ws_uid_of_message)
+            .and_then(|room| room.game.as_ref().map(|game| (game, room.game_seq)))
+            .filter(|(game, _game_seq)| game.is_player(ws_uid_of_message))
+        {
+            info!("send StateSnapshot to: {}", ws_uid_of_message);
+            let snapshot = WireMsg::new(gamestate::snapshot(game, game_seq));
+            rooms_and_users.send_to_user(ws_uid_of_message, &snapshot);
+        }
+    }
+    //the host could have changed while the player was away
+    if let Some(room_code) = rooms_and_users.room_code_of_user(ws_uid_of_message) {
+        let host_changed = WireMsg::new(rooms_and_users.host_changed(&room_code));
+        rooms_and_users.send_to_user(ws_uid_of_message, &host_changed);
+    }
+    //a late member could have taken over the seat of the player while he was away
+    if let Some(ws_uid) = rooms_and_users.seat_taken(ws_uid_of_message) {
+        info!("send SeatTaken to: {}", ws_uid_of_message);
+        let seat_taken = WireMsg::new(WsMessage::SeatTaken { ws_uid });
+        rooms_and_users.send_to_user(ws_uid_of_message, &seat_taken);
+    }
+}
+
+///the host creates a new room and gets the join code
+fn room_create(rooms: &Rooms, ws_uid_of_message: usize) {
+    let mut rooms_and_users = rooms.lock().expect("error rooms.lock()");
+    let room_code = rooms_and_users.create_room(ws_uid_of_message);
+    info!("room created: {} host: {}", room_code, ws_uid_of_message);
+    let wire_msg = WireMsg::new(WsMessage::RoomCreated { room_code });
+    rooms_and_users.send_to_user(ws_uid_of_message, &wire_msg);
+}
+
+///a player joins the room with the join code and gets the pending invite
+fn room_join(rooms: &Rooms, ws_uid_of_message: usize, join_code: &str) {
+    let mut rooms_and_users = rooms.lock().expect("error rooms.lock()");
+    let room_code = join_code.trim().to_uppercase();
+    if rooms_and_users.join_room(ws_uid_of_message, &room_code) {
+        info!("room joined: {} ws_uid: {}", room_code, ws_uid_of_message);
+        let room = unwrap!(rooms_and_users.rooms.get(&room_code));
+        let pending_invite = room.invite.clone();
+        let is_game_in_progress = room.game.as_ref().map_or(false, |game| {
+            game.game_status.as_ref() != GameStatus::GameOverPlayAgainBegin.as_ref()
+        });
+        let wire_msg = WireMsg::new(WsMessage::RoomJoined {
+            room_code,
+            is_game_in_progress,
+        });
+        rooms_and_users.send_to_user(ws_uid_of_message, &wire_msg);
+        if let Some(invite) = pending_invite {
+            rooms_and_users.send_to_user(ws_uid_of_message, &invite);
+        }
+    } else {
+        info!("room not found: {} ws_uid: {}", room_code, ws_uid_of_message);
+        let wire_msg = WireMsg::new(WsMessage::RoomNotFound { room_code });
+        rooms_and_users.send_to_user(ws_uid_of_message, &wire_msg);
+    }
+}
+
+///only the host can invite the members of his room. The invite is kept for the late members.
+///A game in progress must first be over.
+fn invite(rooms: &Rooms, ws_uid_of_message: usize, asked_folder_name: &str, wire_msg: &WireMsg) {
+    let avatars = avatars_of_content(asked_folder_name);
+    let mut rooms_and_users = rooms.lock().expect("error rooms.lock()");
+    let error = match rooms_and_users.room_of_user_mut(ws_uid_of_message) {
+        Some(room) if room.host_ws_uid == ws_uid_of_message => {
+            let is_game_in_progress = room.game.as_ref().map_or(false, |game| {
+                game.game_status.as_ref() != GameStatus::GameOverPlayAgainBegin.as_ref()
+            });
+            if is_game_in_progress {
+                Some((ProtocolErrorCode::InvalidAction, "the game is not over"))
+            } else {
+                room.invite = Some(wire_msg.clone());
+                room.invite_at = Instant::now();
+                room.asked_folder_name = asked_folder_name.to_string();
+                room.avatars = avatars;
+                room.lobby = vec![ws_uid_of_message];
+                //the invite after the game over is a rematch in the same series
+                if let Some(game) = room.game.take() {
+                    room.series = Some(game);
                 }
+                None
             }
         }
+        Some(_room) => Some((ProtocolErrorCode::NotHost, "only the host can invite")),
+        None => Some((ProtocolErrorCode::NotInRoom, "create a room first")),
+    };
+    match error {
+        None => rooms_and_users.send_to_other_members(ws_uid_of_message, wire_msg),
+        Some((code, reason)) => {
+            rooms_and_users.send_protocol_error(ws_uid_of_message, code, reason, "Invite")
+        }
     }
 }
 
-///broadcast is the simplest
-fn broadcast(users: &Users, ws_uid_of_message: usize, new_msg: &str) {
-    // New message from this user, send it to everyone else (except same uid)...
-    // We use `retain` instead of a for loop so that we can reap any user that
-    // appears to have disconnected.
-    info!("broadcast: {}", new_msg);
-    for (&uid, tx) in users.lock().expect("error users.lock()").iter() {
-        if ws_uid_of_message != uid {
-            match tx.unbounded_send(Message::text(String::from(new_msg))) {
-                Ok(()) => (),
-                Err(_disconnected) => {
-                    // The tx is disconnected, our `user_disconnected` code
-                    // should be happening in another task, nothing more to
-                    // do here.
+///the img src of the card images from the game config of the content on the server.
+///The folder name comes from the client, so only a plain name is read.
+fn avatars_of_content(asked_folder_name: &str) -> Vec<String> {
+    if asked_folder_name.is_empty()
+        || !asked_folder_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Vec::new();
+    }
+    std::fs::read_to_string(format!(
+        "{}content/{}/game_config.json",
+        WEB_FOLDER, asked_folder_name
+    ))
+    .ok()
+    .and_then(|json| serde_json::from_str::<GameConfig>(&json).ok())
+    .map_or_else(Vec::new, |game_config| {
+        game_config.avatars(asked_folder_name)
+    })
+}
+
+///the host sends the initial game data. From now on the server owns the game state.
+///The players must be in the lobby of the room, every player only once, and the host must be player1.
+///The server deals the cards itself, the card grid of the host is ignored.
+///A rematch continues the series: the totals are kept and the starting player rotates.
+fn game_data_init(
+    rooms: &Rooms,
+    ws_uid_of_message: usize,
+    game_config: GameConfig,
+    mut players: Vec<Player>,
+) {
+    let mut rooms_and_users = rooms.lock().expect("error rooms.lock()");
+    //the absence comes from the connections, not from the msg of the host
+    let absent_ws_uids: Vec<usize> = players
+        .iter()
+        .map(|p| p.ws_uid)
+        .filter(|&ws_uid| !rooms_and_users.is_connected(ws_uid))
+        .collect();
+    let error = match rooms_and_users.room_of_user_mut(ws_uid_of_message) {
+        Some(room) if room.host_ws_uid == ws_uid_of_message => {
+            if players.first().map(|p| p.ws_uid) != Some(ws_uid_of_message)
+                || players.iter().any(|p| !room.lobby.contains(&p.ws_uid))
+                || players.iter().enumerate().any(|(index, player)| {
+                    players
+                        .iter()
+                        .skip(unwrap!(index.checked_add(1)))
+                        .any(|other| other.ws_uid == player.ws_uid)
+                })
+            {
+                Some((ProtocolErrorCode::InvalidData, "the players are not in the lobby"))
+            } else {
+                info!("new game of the host: {}", ws_uid_of_message);
+                //the game has started, the invite is not valid anymore
+                room.invite = None;
+                let card_grid_data =
+                    build_deck(&mut rand::thread_rng(), &game_config, players.len());
+                room.game_config = Some(game_config);
+                //the host relays the profiles of the others, they must be valid
+                for player in &mut players {
+                    player.drop_invalid_profile(&room.avatars);
+                }
+                let mut game = GameState::new(card_grid_data, players);
+                for ws_uid in absent_ws_uids {
+                    game.set_player_absent(ws_uid, true);
+                }
+                if let Some(last_round) = room.series.as_ref().filter(|last| !last.is_series_over()) {
+                    game.continue_series(last_round);
+                }
+                info!("round {} starting player{}", game.round, game.player_turn);
+                room.game = Some(game);
+                room.turn_clock = Instant::now();
+                //the seq of the game msgs starts again
+                room.game_seq = 0;
+                room.game_msgs.clear();
+                None
+            }
+        }
+        Some(_room) => Some((ProtocolErrorCode::NotHost, "only the host can start the game")),
+        None => Some((ProtocolErrorCode::NotInRoom, "create a room first")),
+    };
+    if let Some((code, reason)) = error {
+        rooms_and_users.send_protocol_error(ws_uid_of_message, code, reason, "GameDataInit");
+        return;
+    }
+    //the host sends the names, colors and avatars of all the players
+    let players_of_game = rooms_and_users
+        .room_of_user(ws_uid_of_message)
+        .and_then(|room| room.game.as_ref())
+        .map(|game| game.players.clone())
+        .unwrap_or_default();
+    for player in &players_of_game {
+        rooms_and_users.set_profile(player);
+    }
+    //the other members get the cards dealt by the server
+    let cards = rooms_and_users.room_of_user(ws_uid_of_message).and_then(|room| {
+        match (room.game.as_ref(), room.game_config.as_ref()) {
+            (Some(game), Some(config)) => Some(WsMessage::GameDataInit {
+                card_grid_data: game.card_grid_data.clone(),
+                game_config: config.clone(),
+                players: game.players.clone(),
+            }),
+            _ => None,
+        }
+    });
+    if let Some(cards_msg) = cards {
+        rooms_and_users.send_to_other_members(ws_uid_of_message, &WireMsg::new(cards_msg));
+    }
+    //the series totals and the starting player come from the server
+    if let Some(game) = rooms_and_users
+        .room_of_user(ws_uid_of_message)
+        .and_then(|room| room.game.as_ref())
+    {
+        let snapshot = WireMsg::new(gamestate::snapshot(game, 0));
+        rooms_and_users.send_to_all_players(ws_uid_of_message, &snapshot);
+    }
+}
+
+///the member declines the invite. Only the host gets the msg.
+fn decline_invite(rooms: &Rooms, ws_uid_of_message: usize, wire_msg: &WireMsg, msg_type: &str) {
+    let mut rooms_and_users = rooms.lock().expect("error rooms.lock()");
+    if !rooms_and_users.decline_invite(ws_uid_of_message, wire_msg) {
+        rooms_and_users.send_protocol_error(
+            ws_uid_of_message,
+            ProtocolErrorCode::NotInRoom,
+            "join a room first",
+            msg_type,
+        );
+    }
+}
+
+///the host changes the lobby. The removed players get KickedFromLobby.
+fn change_lobby(rooms: &Rooms, ws_uid_of_message: usize, lobby: &[usize], msg_type: &str) {
+    let mut rooms_and_users = rooms.lock().expect("error rooms.lock()");
+    match rooms_and_users.change_lobby(ws_uid_of_message, lobby.to_vec()) {
+        Ok(kicked) => {
+            let kicked_msg = WireMsg::new(WsMessage::KickedFromLobby {
+                host_ws_uid: ws_uid_of_message,
+            });
+            for uid in kicked {
+                rooms_and_users.send_to_user(uid, &kicked_msg);
+            }
+        }
+        Err((code, reason)) => {
+            rooms_and_users.send_protocol_error(ws_uid_of_message, code, reason, msg_type)
+        }
+    }
+}
+
+///the member accepts the invite. The other members get the PlayAccept with his validated profile.
+fn play_accept(rooms: &Rooms, ws_uid_of_message: usize, players: &[Player], msg_type: &str) {
+    let mut rooms_and_users = rooms.lock().expect("error rooms.lock()");
+    match rooms_and_users.accept_invite(ws_uid_of_message) {
+        Ok(()) => {
+            //the player sends his own name, color and avatar
+            let valid_players: Vec<Player> = players
+                .iter()
+                .filter(|p| p.ws_uid == ws_uid_of_message)
+                .take(1)
+                .map(|player| rooms_and_users.set_profile(player))
+                .collect();
+            let wire_msg = WireMsg::new(WsMessage::PlayAccept {
+                my_ws_uid: ws_uid_of_message,
+                players: valid_players,
+            });
+            rooms_and_users.send_to_other_members(ws_uid_of_message, &wire_msg);
+        }
+        Err((code, reason)) => {
+            rooms_and_users.send_protocol_error(ws_uid_of_message, code, reason, msg_type)
+        }
+    }
+}
+
+///a late member joins the game in progress. He takes over the slot of an absent player
+///or gets a slice of newly dealt cards. All the players get the StateSnapshot with the new state.
+fn join_in_progress(rooms: &Rooms, ws_uid_of_message: usize, players: &[Player], msg_type: &str) {
+    let mut rooms_and_users = rooms.lock().expect("error rooms.lock()");
+    if let Some(player) = players.iter().find(|p| p.ws_uid == ws_uid_of_message) {
+        rooms_and_users.set_profile(player);
+    }
+    let profile_of_user = rooms_and_users.profile(ws_uid_of_message);
+    let result = match rooms_and_users.room_of_user_mut(ws_uid_of_message) {
+        Some(room) => match (room.game.as_mut(), room.game_config.as_ref()) {
+            (Some(game), Some(game_config))
+                if game.game_status.as_ref() != GameStatus::GameOverPlayAgainBegin.as_ref() =>
+            {
+                let seat = if game.is_player(ws_uid_of_message) {
+                    game.player_number(ws_uid_of_message).ok_or_else(String::new)
+                } else if let Some((absent_player_number, absent_ws_uid)) =
+                    game.take_over_absent_player(ws_uid_of_message)
+                {
+                    info!("seat of {} taken by {}", absent_ws_uid, ws_uid_of_message);
+                    room.seats_taken.push((absent_ws_uid, ws_uid_of_message));
+                    Ok(absent_player_number)
+                } else {
+                    game.add_player_with_new_cards(
+                        &mut rand::thread_rng(),
+                        game_config,
+                        ws_uid_of_message,
+                    )
+                };
+                match seat {
+                    Ok(player_number) => {
+                        info!(
+                            "join in progress: {} player{}",
+                            ws_uid_of_message, player_number
+                        );
+                        if !room.lobby.contains(&ws_uid_of_message) {
+                            room.lobby.push(ws_uid_of_message);
+                        }
+                        if let (Some(profile), Some(player)) = (
+                            profile_of_user.as_ref(),
+                            game.players.get_mut(unwrap!(player_number.checked_sub(1))),
+                        ) {
+                            player.set_profile(profile);
+                        }
+                        Ok((
+                            WsMessage::JoinedInProgress {
+                                asked_folder_name: room.asked_folder_name.clone(),
+                                game_config: game_config.clone(),
+                            },
+                            gamestate::snapshot(game, room.game_seq),
+                        ))
+                    }
+                    Err(e) => Err((ProtocolErrorCode::InvalidAction, e)),
                 }
             }
+            _ => Err((
+                ProtocolErrorCode::NoGame,
+                "there is no game in progress".to_string(),
+            )),
+        },
+        None => Err((ProtocolErrorCode::NotInRoom, "join a room first".to_string())),
+    };
+    match result {
+        Ok((joined, snapshot)) => {
+            rooms_and_users.send_to_user(ws_uid_of_message, &WireMsg::new(joined));
+            rooms_and_users.send_to_all_players(ws_uid_of_message, &WireMsg::new(snapshot));
+        }
+        Err((code, reason)) => {
+            rooms_and_users.send_protocol_error(ws_uid_of_message, code, &reason, msg_type)
+        }
+    }
+}
+
+///apply the player action to the game of his room and send the new state to all players.
+///The action gets the player number of the sender.
+///An action that does not respect the game rules is rejected with a ProtocolError.
+///A duplicate msg_id is ignored, the action was already applied.
+///Only an accepted action records its msg_id, a rejected msg can come again with the same msg_id.
+fn apply_to_game<F>(rooms: &Rooms, ws_uid_of_message: usize, msg_type: &str, msg_id: usize, action: F)
+where
+    F: FnOnce(usize) -> GameAction,
+{
+    let mut rooms_and_users = rooms.lock().expect("error rooms.lock()");
+    if rooms_and_users.is_duplicate_msg_id(ws_uid_of_message, msg_id) {
+        info!(
+            "duplicate {} from ws_uid {} msg_id {}",
+            msg_type, ws_uid_of_message, msg_id
+        );
+        return;
+    }
+    let result = match rooms_and_users.room_of_user_mut(ws_uid_of_message) {
+        Some(Room {
+            game: Some(game),
+            turn_clock,
+            ..
+        }) => match game.player_number(ws_uid_of_message) {
+            Some(player_number) => gamestate::apply_action(
+                game,
+                ws_uid_of_message,
+                &action(player_number),
+                turn_clock,
+            )
+            .map_err(|e| (ProtocolErrorCode::InvalidAction, e)),
+            None => Err((
+                ProtocolErrorCode::InvalidAction,
+                format!("ws_uid {} is not a player", ws_uid_of_message),
+            )),
+        },
+        _ => Err((
+            ProtocolErrorCode::NoGame,
+            "there is no game in progress".to_string(),
+        )),
+    };
+    match result {
+        Ok(msg) => {
+            rooms_and_users.record_msg_id(ws_uid_of_message, msg_id);
+            rooms_and_users.send_game_msg(ws_uid_of_message, msg);
+        }
+        Err((code, reason)) => {
+            rooms_and_users.send_protocol_error(ws_uid_of_message, code, &reason, msg_type)
         }
     }
 }
 
 ///disconnect user
-fn user_disconnected(my_id: usize, users: &Users) {
-    info!("good bye user: {}", my_id);
+fn user_disconnected(my_id: usize, connection_id: usize, rooms: &Rooms) {
+    info!("good bye user: {} connection: {}", my_id, connection_id);
 
-    // Stream closed up, so remove from the user list
-    users.lock().expect("users.lock").remove(&my_id);
+    // Stream closed up. The session stays for reconnect.
+    rooms
+        .lock()
+        .expect("rooms.lock")
+        .disconnect_user(my_id, connection_id);
 }
 //endregion
 
@@ -0,0 +1,1233 @@
+//! rooms.rs - one public server can host many tables at the same time.
+//! A host creates a room and gets a short join code. The other players join with that code.
+//! Invites, accepts and all the game msgs stay inside the room.
+//! The server issues the ws_uid and the session token. Only the session token can reconnect to a ws_uid.
+//! If the host leaves, the host role passes to the next connected member.
+//! The server numbers the game msgs of every game with the seq and keeps the last of them for a resend.
+
+//region: use
+use crate::gamestate;
+use crate::wiremsg::WireMsg;
+
+use futures::sync::mpsc;
+use mem4_common::{
+    GameAction, GameConfig, GameState, Player, ProtocolErrorCode, WsMessage, CAPABILITY_BINARY,
+};
+use rand;
+use rand::Rng;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use warp::ws::Message;
+//endregion
+
+//region: const
+///letters for the join code. Without I and O that look like numbers.
+const ROOM_CODE_LETTERS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
+///length of the join code
+const ROOM_CODE_LENGTH: usize = 4;
+///how many of the last game msgs the room keeps for a resend. An older gap gets the StateSnapshot.
+const GAME_MSG_HISTORY: usize = 32;
+//endregion
+
+///one user with his session. The session stays after a lost connection, so the user can reconnect.
+pub struct User {
+    ///sender of `warp::ws::Message` to this user, None while disconnected
+    pub tx: Option<mpsc::UnboundedSender<Message>>,
+    ///every new WebSocket connection gets a new id
+    pub connection_id: usize,
+    ///unguessable token. Only the owner of the token can reconnect to this ws_uid.
+    pub session_token: String,
+    ///the room this user is a member of
+    pub room_code: Option<String>,
+    ///protocol version of this connection agreed in the Hello, 0 before the Hello
+    pub protocol_version: usize,
+    ///capabilities of this connection agreed in the Hello
+    pub capabilities: Vec<String>,
+    ///the time of the last Pong. A connection without Pong in the pong timeout is dead.
+    pub last_pong: Instant,
+    ///the time the connection was lost, None while connected
+    pub left_at: Option<Instant>,
+    ///the name, color and avatar of the player, from PlayAccept, JoinInProgress or GameDataInit
+    pub profile: Option<Player>,
+    ///msg_id of the last game msg from this user. A msg with the same or lower msg_id is a duplicate.
+    pub last_msg_id: usize,
+}
+
+///one table of players
+pub struct Room {
+    ///ws_uid of the host. He invites the members and starts the game.
+    pub host_ws_uid: usize,
+    ///ws_uid of all the members in the order of joining
+    pub members: Vec<usize>,
+    ///ws_uid of the members that have accepted the invite, the host is the first
+    pub lobby: Vec<usize>,
+    ///the last Invite. It is sent also to the members that join later.
+    pub invite: Option<WireMsg>,
+    ///the time of the last Invite. The invite expires after the invite timeout.
+    pub invite_at: Instant,
+    ///the content folder of the last Invite
+    pub asked_folder_name: String,
+    ///the img src of the card images of the content of the last Invite. Only they are valid avatars.
+    pub avatars: Vec<String>,
+    ///the game config of the game in progress. The late players need it.
+    pub game_config: Option<GameConfig>,
+    ///the authoritative state of the game in progress
+    pub game: Option<GameState>,
+    ///the last finished round. The rematch continues its series.
+    pub series: Option<GameState>,
+    ///the time of the last action in the game. The play time goes to the player in turn.
+    pub turn_clock: Instant,
+    ///seq of the last game msg sent to the players. It starts from 0 with every game.
+    pub game_seq: usize,
+    ///the last game msgs with their seq, for the ResendRequest
+    pub game_msgs: VecDeque<(usize, WireMsg)>,
+    ///the absent players with the seat taken by a late member and the ws_uid of the late member.
+    ///They get SeatTaken after their next Hello.
+    pub seats_taken: Vec<(usize, usize)>,
+}
+
+///all the connected users and their rooms
+#[derive(Default)]
+pub struct RoomsAndUsers {
+    /// - Key is the ws_uid of the user
+    pub users: HashMap<usize, User>,
+    /// - Key is the join code of the room
+    pub rooms: HashMap<String, Room>,
+    ///the last ws_uid issued by the server
+    pub last_ws_uid: usize,
+    ///the last connection id issued by the server
+    pub last_connection_id: usize,
+}
+
+impl RoomsAndUsers {
+    ///new connection. With a valid session token the user gets back his ws_uid and room.
+    ///Else the server issues a new unique ws_uid and a new session token.
+    ///Returns ws_uid and connection_id.
+    pub fn connect_user(
+        &mut self,
+        session_token: &str,
+        tx: mpsc::UnboundedSender<Message>,
+    ) -> (usize, usize) {
+        self.last_connection_id = unwrap!(self.last_connection_id.checked_add(1));
+        let connection_id = self.last_connection_id;
+        let old_ws_uid = self
+            .users
+            .iter()
+            .find(|(_uid, user)| user.session_token == session_token)
+            .map(|(&uid, _user)| uid);
+        if let Some(ws_uid) = old_ws_uid {
+            //the old tx is dropped and that closes the old connection
+            let user = unwrap!(self.users.get_mut(&ws_uid));
+            let is_rejoin = user.tx.is_none() && user.room_code.is_some();
+            user.left_at = None;
+            user.tx = Some(tx);
+            user.connection_id = connection_id;
+            //the new connection must send its own Hello
+            user.protocol_version = 0;
+            user.capabilities.clear();
+            user.last_pong = Instant::now();
+            if is_rejoin {
+                self.player_rejoined(ws_uid);
+            }
+            (ws_uid, connection_id)
+        } else {
+            self.last_ws_uid = unwrap!(self.last_ws_uid.checked_add(1));
+            let ws_uid = self.last_ws_uid;
+            self.users.insert(
+                ws_uid,
+                User {
+                    tx: Some(tx),
+                    connection_id,
+                    session_token: new_session_token(),
+                    room_code: None,
+                    protocol_version: 0,
+                    capabilities: Vec::new(),
+                    last_pong: Instant::now(),
+                    left_at: None,
+                    profile: None,
+                    last_msg_id: 0,
+                },
+            );
+            (ws_uid, connection_id)
+        }
+    }
+
+    ///the stream of the connection has ended.
+    ///A newer connection of the same user or a connection already declared dead is not affected.
+    pub fn disconnect_user(&mut self, ws_uid: usize, connection_id: usize) {
+        match self.users.get(&ws_uid) {
+            Some(user) if user.connection_id == connection_id && user.tx.is_some() => {
+                self.connection_lost(ws_uid)
+            }
+            _ => (),
+        }
+    }
+
+    ///the Pong from the client: the connection is alive
+    pub fn pong(&mut self, ws_uid: usize) {
+        if let Some(user) = self.users.get_mut(&ws_uid) {
+            user.last_pong = Instant::now();
+        }
+    }
+
+    ///send a Ping to all connected users after their Hello
+    pub fn ping_all(&self, ping_id: usize) {
+        let wire_msg = WireMsg::new(WsMessage::Ping { ping_id });
+        for (&ws_uid, user) in &self.users {
+            if user.tx.is_some() && user.protocol_version != 0 {
+                self.send_to_user(ws_uid, &wire_msg);
+            }
+        }
+    }
+
+    ///the connections without a Pong in the pong timeout are dead
+    pub fn reap_dead_connections(&mut self, pong_timeout: Duration) {
+        let dead: Vec<usize> = self
+            .users
+            .iter()
+            .filter(|(_uid, user)| user.tx.is_some() && user.last_pong.elapsed() > pong_timeout)
+            .map(|(&uid, _user)| uid)
+            .collect();
+        for ws_uid in dead {
+            info!("connection dead: {}", ws_uid);
+            //connection_lost closes the connection. The close tells a half-open client to reconnect.
+            self.connection_lost(ws_uid);
+        }
+    }
+
+    ///the connection is lost or dead. The session stays for reconnect if the user is in a room.
+    ///The other members of the room get PlayerLeft and the turns skip the absent player.
+    fn connection_lost(&mut self, ws_uid: usize) {
+        let is_in_room = match self.users.get_mut(&ws_uid) {
+            Some(user) => {
+                user.tx = None;
+                user.left_at = Some(Instant::now());
+                user.room_code.is_some()
+            }
+            None => return,
+        };
+        if is_in_room {
+            if let Some(game) = self
+                .room_of_user_mut(ws_uid)
+                .and_then(|room| room.game.as_mut())
+            {
+                game.set_player_absent(ws_uid, true);
+            }
+            let wire_msg = WireMsg::new(WsMessage::PlayerLeft { ws_uid });
+            self.send_to_other_members(ws_uid, &wire_msg);
+            //if nobody in the room is connected, the room is over
+            let room_code = unwrap!(self.room_code_of_user(ws_uid));
+            let room = unwrap!(self.rooms.get(&room_code));
+            let members = room.members.clone();
+            let is_host = room.host_ws_uid == ws_uid;
+            if members.iter().all(|&uid| !self.is_connected(uid)) {
+                for uid in members {
+                    self.remove_user(uid);
+                }
+            } else if is_host {
+                self.pass_host_role(&room_code);
+            } else {
+                //the other members stay in the room with the same host
+            }
+        } else {
+            self.remove_user(ws_uid);
+        }
+    }
+
+    ///drop the tx of the user. The forwarding stream ends and that closes the websocket.
+    pub fn close_connection(&mut self, ws_uid: usize) {
+        if let Some(user) = self.users.get_mut(&ws_uid) {
+            user.tx = None;
+        }
+    }
+
+    ///the absent player has reconnected. The other members of the room get PlayerRejoined.
+    fn player_rejoined(&mut self, ws_uid: usize) {
+        info!("player rejoined: {}", ws_uid);
+        if let Some(game) = self
+            .room_of_user_mut(ws_uid)
+            .and_then(|room| room.game.as_mut())
+        {
+            game.set_player_absent(ws_uid, false);
+        }
+        let wire_msg = WireMsg::new(WsMessage::PlayerRejoined { ws_uid });
+        self.send_to_other_members(ws_uid, &wire_msg);
+    }
+
+    ///the game must not wait forever for an absent player.
+    ///After the grace period the server skips his turn and sends the new state to all players.
+    pub fn skip_absent_turns(&mut self, absent_grace: Duration) {
+        let mut absent_in_turn = Vec::new();
+        for room in self.rooms.values() {
+            if let Some(game) = &room.game {
+                if game.is_turn_of_absent_player() {
+                    let ws_uid =
+                        unwrap!(game.players.get(unwrap!(game.player_turn.checked_sub(1)))).ws_uid;
+                    let is_grace_over = self
+                        .users
+                        .get(&ws_uid)
+                        .and_then(|user| user.left_at)
+                        .map_or(true, |left_at| left_at.elapsed() >= absent_grace);
+                    if is_grace_over {
+                        absent_in_turn.push(ws_uid);
+                    }
+                }
+            }
+        }
+        for ws_uid in absent_in_turn {
+            let result = match self.room_of_user_mut(ws_uid) {
+                Some(Room {
+                    game: Some(game),
+                    turn_clock,
+                    ..
+                }) => gamestate::apply_action(game, 0, &GameAction::SkipTurn, turn_clock),
+                _ => continue,
+            };
+            match result {
+                Ok(msg) => {
+                    info!("turn skipped: {}", ws_uid);
+                    self.send_game_msg(ws_uid, msg);
+                }
+                Err(e) => info!("turn not skipped: {} {}", ws_uid, e),
+            }
+        }
+    }
+
+    ///the host role passes to the next connected member of the room.
+    ///The new host is the first in the lobby and the pending invite is now his. The old host leaves the lobby.
+    ///All the members get HostChanged and LobbyChanged.
+    fn pass_host_role(&mut self, room_code: &str) {
+        let new_host = match self.rooms.get(room_code) {
+            Some(room) => {
+                let others: Vec<usize> = room
+                    .members
+                    .iter()
+                    .cloned()
+                    .filter(|&uid| uid != room.host_ws_uid)
+                    .collect();
+                match others
+                    .iter()
+                    .cloned()
+                    .find(|&uid| self.is_connected(uid))
+                    .or_else(|| others.first().cloned())
+                {
+                    Some(uid) => uid,
+                    None => return,
+                }
+            }
+            None => return,
+        };
+        let room = unwrap!(self.rooms.get_mut(room_code));
+        info!(
+            "host changed: {} {} -> {}",
+            room_code, room.host_ws_uid, new_host
+        );
+        let old_host = room.host_ws_uid;
+        room.host_ws_uid = new_host;
+        room.lobby.retain(|&uid| uid != new_host && uid != old_host);
+        room.lobby.insert(0, new_host);
+        let pending_invite = room.invite.take();
+        room.invite = pending_invite.map(|invite| {
+            #[allow(clippy::wildcard_enum_match_arm)]
+            let asked_folder_name = match invite.msg() {
+                WsMessage::Invite {
+                    asked_folder_name, ..
+                } => asked_folder_name.clone(),
+                _ => return invite,
+            };
+            WireMsg::new(WsMessage::Invite {
+                my_ws_uid: new_host,
+                asked_folder_name,
+            })
+        });
+        let members = room.members.clone();
+        let lobby_changed = WireMsg::new(WsMessage::LobbyChanged {
+            my_ws_uid: new_host,
+            lobby: room.lobby.clone(),
+        });
+        let host_changed = WireMsg::new(self.host_changed(room_code));
+        for uid in members {
+            self.send_to_user(uid, &host_changed);
+            self.send_to_user(uid, &lobby_changed);
+        }
+    }
+
+    ///the msg HostChanged with the host and the lobby of the room
+    pub fn host_changed(&self, room_code: &str) -> WsMessage {
+        let room = unwrap!(self.rooms.get(room_code));
+        WsMessage::HostChanged {
+            host_ws_uid: room.host_ws_uid,
+            players: room
+                .lobby
+                .iter()
+                .map(|&uid| {
+                    let mut player = Player::new(uid);
+                    if let Some(profile) = self.profile(uid) {
+                        player.set_profile(&profile);
+                    }
+                    player.is_absent = !self.is_connected(uid);
+                    player
+                })
+                .collect(),
+        }
+    }
+
+    ///the host withdraws the invite. The members get InviteCancel.
+    pub fn cancel_invite(
+        &mut self,
+        ws_uid: usize,
+    ) -> Result<(), (ProtocolErrorCode, &'static str)> {
+        match self.room_of_user_mut(ws_uid) {
+            Some(room) if room.host_ws_uid == ws_uid => {
+                room.invite = None;
+                room.lobby = vec![ws_uid];
+            }
+            Some(_room) => {
+                return Err((
+                    ProtocolErrorCode::NotHost,
+                    "only the host can cancel the invite",
+                ))
+            }
+            None => return Err((ProtocolErrorCode::NotInRoom, "create a room first")),
+        }
+        let wire_msg = WireMsg::new(WsMessage::InviteCancel {
+            my_ws_uid: ws_uid,
+            is_expired: false,
+        });
+        self.send_to_other_members(ws_uid, &wire_msg);
+        Ok(())
+    }
+
+    ///the member declines the invite and leaves the lobby. Only the host gets the msg.
+    ///Returns false if he is not in a room.
+    pub fn decline_invite(&mut self, ws_uid: usize, wire_msg: &WireMsg) -> bool {
+        let host_ws_uid = match self.room_of_user_mut(ws_uid) {
+            Some(room) => {
+                room.lobby.retain(|&uid| uid != ws_uid);
+                room.host_ws_uid
+            }
+            None => return false,
+        };
+        self.send_to_user(host_ws_uid, wire_msg);
+        true
+    }
+
+    ///the invites without a start of the game in the invite timeout expire.
+    ///All the members, also the host, get InviteCancel.
+    pub fn expire_invites(&mut self, invite_timeout: Duration) {
+        let mut expired = Vec::new();
+        for room in self.rooms.values_mut() {
+            if room.invite.is_some() && room.invite_at.elapsed() >= invite_timeout {
+                info!("invite expired: host {}", room.host_ws_uid);
+                room.invite = None;
+                room.lobby = vec![room.host_ws_uid];
+                expired.push((room.host_ws_uid, room.members.clone()));
+            }
+        }
+        for (host_ws_uid, members) in expired {
+            let wire_msg = WireMsg::new(WsMessage::InviteCancel {
+                my_ws_uid: host_ws_uid,
+                is_expired: true,
+            });
+            for uid in members {
+                self.send_to_user(uid, &wire_msg);
+            }
+        }
+    }
+
+    ///the member has accepted the invite and waits in the lobby.
+    ///Without a pending invite the lobby is not changed and it returns the error for the ProtocolError.
+    pub fn accept_invite(
+        &mut self,
+        ws_uid: usize,
+    ) -> Result<(), (ProtocolErrorCode, &'static str)> {
+        match self.room_of_user_mut(ws_uid) {
+            Some(room) if room.invite.is_some() => {
+                if !room.lobby.contains(&ws_uid) {
+                    room.lobby.push(ws_uid);
+                }
+                Ok(())
+            }
+            Some(_room) => Err((
+                ProtocolErrorCode::InvalidData,
+                "there is no invite to accept",
+            )),
+            None => Err((ProtocolErrorCode::NotInRoom, "join a room first")),
+        }
+    }
+
+    ///the host reorders the lobby or removes players from it. The host stays the first.
+    ///Returns the removed players or the error for the ProtocolError.
+    pub fn change_lobby(
+        &mut self,
+        ws_uid: usize,
+        lobby: Vec<usize>,
+    ) -> Result<Vec<usize>, (ProtocolErrorCode, &'static str)> {
+        match self.room_of_user_mut(ws_uid) {
+            Some(room) if room.host_ws_uid == ws_uid => {
+                if lobby.first() != Some(&ws_uid)
+                    || lobby.iter().any(|uid| !room.lobby.contains(uid))
+                    || lobby.iter().enumerate().any(|(index, uid)| {
+                        lobby
+                            .iter()
+                            .skip(unwrap!(index.checked_add(1)))
+                            .any(|other| other == uid)
+                    })
+                {
+                    return Err((
+                        ProtocolErrorCode::InvalidData,
+                        "the players are not in the lobby",
+                    ));
+                }
+                let kicked = room
+                    .lobby
+                    .iter()
+                    .filter(|uid| !lobby.contains(uid))
+                    .cloned()
+                    .collect();
+                room.lobby = lobby;
+                Ok(kicked)
+            }
+            Some(_room) => Err((
+                ProtocolErrorCode::NotHost,
+                "only the host can change the lobby",
+            )),
+            None => Err((ProtocolErrorCode::NotInRoom, "create a room first")),
+        }
+    }
+
+    ///has this user a live connection
+    pub fn is_connected(&self, ws_uid: usize) -> bool {
+        self.users
+            .get(&ws_uid)
+            .map_or(false, |user| user.tx.is_some())
+    }
+
+    ///remember the name, color and avatar of the player. The invalid values drop back to the defaults.
+    ///Returns the valid profile for the other members.
+    pub fn set_profile(&mut self, profile: &Player) -> Player {
+        let mut valid_profile = profile.clone();
+        valid_profile.drop_invalid_profile(
+            self.room_of_user(profile.ws_uid)
+                .map_or(&[], |room| room.avatars.as_slice()),
+        );
+        if let Some(user) = self.users.get_mut(&profile.ws_uid) {
+            user.profile = Some(valid_profile.clone());
+        }
+        valid_profile
+    }
+
+    ///the name, color and avatar of the player, if he has sent them
+    pub fn profile(&self, ws_uid: usize) -> Option<Player> {
+        self.users
+            .get(&ws_uid)
+            .and_then(|user| user.profile.clone())
+    }
+
+    ///the ws_uid of the late member that has taken over the seat of this player.
+    ///The player is notified only once.
+    pub fn seat_taken(&mut self, ws_uid: usize) -> Option<usize> {
+        let room = self.room_of_user_mut(ws_uid)?;
+        let index = room
+            .seats_taken
+            .iter()
+            .position(|&(uid, _late_ws_uid)| uid == ws_uid)?;
+        Some(room.seats_taken.remove(index).1)
+    }
+
+    ///the user leaves the room and the session is over
+    pub fn remove_user(&mut self, ws_uid: usize) {
+        self.leave_room(ws_uid);
+        self.users.remove(&ws_uid);
+    }
+
+    ///the session token of this user
+    pub fn session_token_of_user(&self, ws_uid: usize) -> String {
+        self.users
+            .get(&ws_uid)
+            .map_or_else(String::new, |user| user.session_token.clone())
+    }
+
+    ///the protocol version of the connection of this user, 0 before the Hello
+    pub fn protocol_version_of_user(&self, ws_uid: usize) -> usize {
+        self.users
+            .get(&ws_uid)
+            .map_or(0, |user| user.protocol_version)
+    }
+
+    ///has the connection of this user this capability
+    pub fn has_capability(&self, ws_uid: usize, capability: &str) -> bool {
+        self.users.get(&ws_uid).map_or(false, |user| {
+            user.capabilities.iter().any(|c| c == capability)
+        })
+    }
+
+    ///create a new room with a new unique join code. The host leaves his old room.
+    pub fn create_room(&mut self, host_ws_uid: usize) -> String {
+        self.leave_room(host_ws_uid);
+        let mut rng = rand::thread_rng();
+        let room_code = loop {
+            let code: String = (0..ROOM_CODE_LENGTH)
+                .map(|_| {
+                    char::from(*unwrap!(
+                        ROOM_CODE_LETTERS.get(rng.gen_range(0, ROOM_CODE_LETTERS.len()))
+                    ))
+                })
+                .collect();
+            if !self.rooms.contains_key(&code) {
+                break code;
+            }
+        };
+        self.rooms.insert(
+            room_code.clone(),
+            Room {
+                host_ws_uid,
+                members: vec![host_ws_uid],
+                lobby: vec![host_ws_uid],
+                invite: None,
+                invite_at: Instant::now(),
+                asked_folder_name: String::new(),
+                avatars: Vec::new(),
+                game_config: None,
+                game: None,
+                series: None,
+                turn_clock: Instant::now(),
+                game_seq: 0,
+                game_msgs: VecDeque::new(),
+                seats_taken: Vec::new(),
+            },
+        );
+        if let Some(user) = self.users.get_mut(&host_ws_uid) {
+            user.room_code = Some(room_code.clone());
+        }
+        room_code
+    }
+
+    ///join an existing room. Returns false if the join code is unknown.
+    pub fn join_room(&mut self, ws_uid: usize, room_code: &str) -> bool {
+        if !self.rooms.contains_key(room_code) {
+            return false;
+        }
+        if self.room_code_of_user(ws_uid).as_ref().map(String::as_str) == Some(room_code) {
+            return true;
+        }
+        self.leave_room(ws_uid);
+        unwrap!(self.rooms.get_mut(room_code)).members.push(ws_uid);
+        if let Some(user) = self.users.get_mut(&ws_uid) {
+            user.room_code = Some(room_code.to_string());
+        }
+        true
+    }
+
+    ///leave the room. The empty room is removed. If the host leaves, the host role passes on.
+    pub fn leave_room(&mut self, ws_uid: usize) {
+        if let Some(room_code) = self.room_code_of_user(ws_uid) {
+            let (is_empty, is_host) = match self.rooms.get_mut(&room_code) {
+                Some(room) => {
+                    room.members.retain(|&uid| uid != ws_uid);
+                    room.lobby.retain(|&uid| uid != ws_uid);
+                    room.seats_taken
+                        .retain(|&(uid, _late_ws_uid)| uid != ws_uid);
+                    (room.members.is_empty(), room.host_ws_uid == ws_uid)
+                }
+                None => (false, false),
+            };
+            if is_empty {
+                info!("room removed: {}", room_code);
+                self.rooms.remove(&room_code);
+            } else if is_host {
+                self.pass_host_role(&room_code);
+            } else {
+                //the other members stay in the room with the same host
+            }
+        }
+        if let Some(user) = self.users.get_mut(&ws_uid) {
+            user.room_code = None;
+        }
+    }
+
+    ///the join code of the room of this user
+    pub fn room_code_of_user(&self, ws_uid: usize) -> Option<String> {
+        self.users
+            .get(&ws_uid)
+            .and_then(|user| user.room_code.clone())
+    }
+
+    ///the room of this user
+    pub fn room_of_user(&self, ws_uid: usize) -> Option<&Room> {
+        self.room_code_of_user(ws_uid)
+            .and_then(move |room_code| self.rooms.get(&room_code))
+    }
+
+    ///the mutable room of this user
+    pub fn room_of_user_mut(&mut self, ws_uid: usize) -> Option<&mut Room> {
+        match self.room_code_of_user(ws_uid) {
+            Some(room_code) => self.rooms.get_mut(&room_code),
+            None => None,
+        }
+    }
+
+    ///send the msg to one user. A connection with the binary capability gets the msg in bincode.
+    ///The msg is serialized only once for every wire format, also for many users.
+    pub fn send_to_user(&self, ws_uid: usize, wire_msg: &WireMsg) {
+        if let Some(tx) = self.users.get(&ws_uid).and_then(|user| user.tx.as_ref()) {
+            let message = if self.has_capability(ws_uid, CAPABILITY_BINARY) {
+                Message::binary(wire_msg.binary())
+            } else {
+                Message::text(wire_msg.json())
+            };
+            match tx.unbounded_send(message) {
+                Ok(()) => (),
+                Err(_disconnected) => {
+                    // The tx is disconnected, our `user_disconnected` code
+                    // should be happening in another task, nothing more to
+                    // do here.
+                }
+            }
+        }
+    }
+
+    ///the msg of the sender cannot be parsed, validated or routed. The sender gets the error back.
+    pub fn send_protocol_error(
+        &self,
+        ws_uid: usize,
+        code: ProtocolErrorCode,
+        reason: &str,
+        msg_type: &str,
+    ) {
+        info!(
+            "ProtocolError to ws_uid {}: {} {} {}",
+            ws_uid, msg_type, code, reason
+        );
+        let wire_msg = WireMsg::new(WsMessage::ProtocolError {
+            code,
+            reason: reason.to_string(),
+            msg_type: msg_type.to_string(),
+        });
+        self.send_to_user(ws_uid, &wire_msg);
+    }
+
+    ///send the msg to all the other members of the room of the sender
+    pub fn send_to_other_members(&self, ws_uid_of_message: usize, wire_msg: &WireMsg) {
+        if let Some(room) = self.room_of_user(ws_uid_of_message) {
+            for &uid in &room.members {
+                if uid != ws_uid_of_message {
+                    self.send_to_user(uid, wire_msg);
+                }
+            }
+        }
+    }
+
+    ///the game msg gets the next seq of the game. The room keeps it for a resend and sends it to all players.
+    pub fn send_game_msg(&mut self, ws_uid_of_message: usize, mut msg: WsMessage) {
+        if let Some(room) = self.room_of_user_mut(ws_uid_of_message) {
+            room.game_seq = unwrap!(room.game_seq.checked_add(1));
+            msg.set_seq(room.game_seq);
+            room.game_msgs.push_back((room.game_seq, WireMsg::new(msg)));
+            if room.game_msgs.len() > GAME_MSG_HISTORY {
+                room.game_msgs.pop_front();
+            }
+        }
+        if let Some((_seq, wire_msg)) = self
+            .room_of_user(ws_uid_of_message)
+            .and_then(|room| room.game_msgs.back())
+        {
+            self.send_to_all_players(ws_uid_of_message, wire_msg);
+        }
+    }
+
+    ///the player has missed the game msgs from from_seq. He gets them again from the history.
+    ///If they are not in the history anymore, he gets the StateSnapshot instead.
+    pub fn resend_game_msgs(&self, ws_uid: usize, from_seq: usize) {
+        let room = match self.room_of_user(ws_uid) {
+            Some(room) => room,
+            None => return,
+        };
+        if !room
+            .game
+            .as_ref()
+            .map_or(false, |game| game.is_player(ws_uid))
+        {
+            return;
+        }
+        let is_in_history = room
+            .game_msgs
+            .front()
+            .map_or(false, |(seq, _wire_msg)| *seq <= from_seq);
+        if is_in_history {
+            info!("resend to: {} from seq {}", ws_uid, from_seq);
+            for (_seq, wire_msg) in room
+                .game_msgs
+                .iter()
+                .filter(|(seq, _wire_msg)| *seq >= from_seq)
+            {
+                self.send_to_user(ws_uid, wire_msg);
+            }
+        } else {
+            self.send_state_snapshot(ws_uid);
+        }
+    }
+
+    ///the authoritative state for a player with a desync or an old gap
+    pub fn send_state_snapshot(&self, ws_uid: usize) {
+        if let Some(room) = self.room_of_user(ws_uid) {
+            if let Some(game) = room.game.as_ref().filter(|game| game.is_player(ws_uid)) {
+                info!("send StateSnapshot to: {} seq {}", ws_uid, room.game_seq);
+                let wire_msg = WireMsg::new(gamestate::snapshot(game, room.game_seq));
+                self.send_to_user(ws_uid, &wire_msg);
+            }
+        }
+    }
+
+    ///a game msg with an already seen msg_id is a duplicate, for example sent again from the outbox.
+    ///A msg without msg_id is never a duplicate.
+    pub fn is_duplicate_msg_id(&self, ws_uid: usize, msg_id: usize) -> bool {
+        match self.users.get(&ws_uid) {
+            Some(user) => msg_id != 0 && msg_id <= user.last_msg_id,
+            None => false,
+        }
+    }
+
+    ///remember the msg_id of an accepted game msg. A rejected msg does not use up its msg_id.
+    pub fn record_msg_id(&mut self, ws_uid: usize, msg_id: usize) {
+        if let Some(user) = self.users.get_mut(&ws_uid) {
+            if msg_id > user.last_msg_id {
+                user.last_msg_id = msg_id;
+            }
+        }
+    }
+
+    ///send the new state of the game to all the players of the room, also to the sender
+    pub fn send_to_all_players(&self, ws_uid_of_message: usize, wire_msg: &WireMsg) {
+        if let Some(room) = self.room_of_user(ws_uid_of_message) {
+            if let Some(game) = &room.game {
+                for &uid in &room.members {
+                    if game.is_player(uid) {
+                        self.send_to_user(uid, wire_msg);
+                    }
+                }
+            }
+        }
+    }
+}
+
+///unguessable session token: 128 random bits from a cryptographically secure generator
+fn new_session_token() -> String {
+    let mut rng = rand::thread_rng();
+    format!("{:016x}{:016x}", rng.gen::<u64>(), rng.gen::<u64>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::Stream;
+    use mem4_common::{PLAYER_COLORS, PLAYER_NAME_MAX_CHARS};
+
+    ///the users with the ws_uid from 1 are connected, but not yet in a room
+    fn new_rooms(users_count: usize) -> RoomsAndUsers {
+        let mut rooms = RoomsAndUsers::default();
+        for _ in 0..users_count {
+            connect(&mut rooms, "");
+        }
+        rooms
+    }
+
+    ///the Invite of the host
+    fn invite(host_ws_uid: usize) -> WireMsg {
+        WireMsg::new(WsMessage::Invite {
+            my_ws_uid: host_ws_uid,
+            asked_folder_name: String::new(),
+        })
+    }
+
+    ///a new connection with the session token. Returns ws_uid and connection_id.
+    fn connect(rooms: &mut RoomsAndUsers, session_token: &str) -> (usize, usize) {
+        let (tx, _rx) = mpsc::unbounded();
+        rooms.connect_user(session_token, tx)
+    }
+
+    ///the ProtocolErrorCode of the rejected lobby change
+    fn lobby_error(result: Result<Vec<usize>, (ProtocolErrorCode, &'static str)>) -> String {
+        unwrap!(result.err()).0.as_ref().to_string()
+    }
+
+    #[test]
+    fn create_room_gives_a_join_code() {
+        let mut rooms = new_rooms(1);
+        let room_code = rooms.create_room(1);
+        assert_eq!(room_code.len(), ROOM_CODE_LENGTH);
+        assert!(room_code.bytes().all(|b| ROOM_CODE_LETTERS.contains(&b)));
+        assert_eq!(rooms.room_code_of_user(1), Some(room_code.clone()));
+        assert_eq!(unwrap!(rooms.rooms.get(&room_code)).members, vec![1]);
+        assert_eq!(unwrap!(rooms.room_of_user(1)).host_ws_uid, 1);
+    }
+
+    #[test]
+    fn join_room_with_the_join_code() {
+        let mut rooms = new_rooms(2);
+        let room_code = rooms.create_room(1);
+        assert!(!rooms.join_room(2, "XXXX"));
+        assert_eq!(rooms.room_code_of_user(2), None);
+        assert!(rooms.join_room(2, &room_code));
+        //joining again does not add the member twice
+        assert!(rooms.join_room(2, &room_code));
+        assert_eq!(unwrap!(rooms.room_of_user(2)).members, vec![1, 2]);
+    }
+
+    #[test]
+    fn leave_room_removes_the_empty_room() {
+        let mut rooms = new_rooms(2);
+        let room_code = rooms.create_room(1);
+        assert!(rooms.join_room(2, &room_code));
+        rooms.leave_room(2);
+        assert_eq!(rooms.room_code_of_user(2), None);
+        assert_eq!(unwrap!(rooms.rooms.get(&room_code)).members, vec![1]);
+        rooms.remove_user(1);
+        assert!(rooms.rooms.is_empty());
+        assert!(rooms.users.contains_key(&2));
+    }
+
+    #[test]
+    fn create_room_leaves_the_old_room() {
+        let mut rooms = new_rooms(2);
+        let old_room_code = rooms.create_room(1);
+        assert!(rooms.join_room(2, &old_room_code));
+        let new_room_code = rooms.create_room(2);
+        assert_ne!(old_room_code, new_room_code);
+        assert_eq!(unwrap!(rooms.rooms.get(&old_room_code)).members, vec![1]);
+        assert_eq!(unwrap!(rooms.rooms.get(&new_room_code)).members, vec![2]);
+    }
+
+    #[test]
+    fn connect_user_issues_unique_ws_uids() {
+        let mut rooms = RoomsAndUsers::default();
+        let (ws_uid_1, connection_1) = connect(&mut rooms, "");
+        let (ws_uid_2, connection_2) = connect(&mut rooms, "unknown token");
+        assert_ne!(ws_uid_1, ws_uid_2);
+        assert_ne!(connection_1, connection_2);
+        assert_ne!(rooms.session_token_of_user(ws_uid_1), "");
+        assert_ne!(
+            rooms.session_token_of_user(ws_uid_1),
+            rooms.session_token_of_user(ws_uid_2)
+        );
+    }
+
+    #[test]
+    fn close_connection_ends_the_stream_after_the_sent_msgs() {
+        let mut rooms = RoomsAndUsers::default();
+        let (tx, rx) = mpsc::unbounded();
+        let (ws_uid, _connection_id) = rooms.connect_user("", tx);
+        rooms.send_to_user(ws_uid, &WireMsg::new(WsMessage::Ping { ping_id: 1 }));
+        rooms.close_connection(ws_uid);
+        //the stream ends without rx.close(), because the tx is dropped
+        let msgs: Vec<Message> = rx.wait().filter_map(Result::ok).collect();
+        assert_eq!(msgs.len(), 1);
+    }
+
+    #[test]
+    fn reconnect_with_the_session_token() {
+        let mut rooms = new_rooms(2);
+        let room_code = rooms.create_room(1);
+        assert!(rooms.join_room(2, &room_code));
+        rooms.disconnect_user(2, 2);
+        assert!(unwrap!(rooms.users.get(&2)).tx.is_none());
+        let session_token = rooms.session_token_of_user(2);
+        let (ws_uid, connection_id) = connect(&mut rooms, &session_token);
+        assert_eq!(ws_uid, 2);
+        assert_eq!(connection_id, 3);
+        assert_eq!(rooms.room_code_of_user(2), Some(room_code));
+    }
+
+    #[test]
+    fn disconnect_of_an_old_connection_is_ignored() {
+        let mut rooms = new_rooms(2);
+        let room_code = rooms.create_room(1);
+        assert!(rooms.join_room(2, &room_code));
+        let session_token = rooms.session_token_of_user(2);
+        let (_ws_uid, connection_id) = connect(&mut rooms, &session_token);
+        rooms.disconnect_user(2, 2);
+        assert!(unwrap!(rooms.users.get(&2)).tx.is_some());
+        assert_eq!(unwrap!(rooms.users.get(&2)).connection_id, connection_id);
+    }
+
+    #[test]
+    fn disconnect_ends_the_session_outside_of_a_room() {
+        let mut rooms = new_rooms(3);
+        rooms.disconnect_user(3, 3);
+        assert!(!rooms.users.contains_key(&3));
+        //the room is over when nobody in it is connected
+        let room_code = rooms.create_room(1);
+        assert!(rooms.join_room(2, &room_code));
+        rooms.disconnect_user(1, 1);
+        assert!(rooms.users.contains_key(&1));
+        rooms.disconnect_user(2, 2);
+        assert!(rooms.users.is_empty());
+        assert!(rooms.rooms.is_empty());
+    }
+
+    #[test]
+    fn skip_absent_turns_after_the_grace_period() {
+        let mut rooms = new_rooms(3);
+        let room_code = rooms.create_room(1);
+        assert!(rooms.join_room(2, &room_code));
+        assert!(rooms.join_room(3, &room_code));
+        let players = (1..=3).map(Player::new).collect();
+        let mut game = GameState::new(Vec::new(), players);
+        game.player_turn = 2;
+        unwrap!(rooms.room_of_user_mut(1)).game = Some(game);
+        rooms.disconnect_user(2, 2);
+        rooms.skip_absent_turns(Duration::from_secs(60));
+        assert_eq!(
+            unwrap!(unwrap!(rooms.room_of_user(1)).game.as_ref()).player_turn,
+            2
+        );
+        rooms.skip_absent_turns(Duration::from_secs(0));
+        assert_eq!(
+            unwrap!(unwrap!(rooms.room_of_user(1)).game.as_ref()).player_turn,
+            3
+        );
+    }
+
+    #[test]
+    fn host_leaves_the_host_role_passes_on() {
+        let mut rooms = new_rooms(3);
+        let room_code = rooms.create_room(1);
+        assert!(rooms.join_room(2, &room_code));
+        assert!(rooms.join_room(3, &room_code));
+        rooms.leave_room(1);
+        let room = unwrap!(rooms.rooms.get(&room_code));
+        assert_eq!(room.host_ws_uid, 2);
+        assert_eq!(room.lobby.first(), Some(&2));
+        assert!(!room.lobby.contains(&1));
+    }
+
+    #[test]
+    fn host_disconnects_the_next_connected_member_is_host() {
+        let mut rooms = new_rooms(3);
+        let room_code = rooms.create_room(1);
+        assert!(rooms.join_room(2, &room_code));
+        assert!(rooms.join_room(3, &room_code));
+        rooms.disconnect_user(2, 2);
+        rooms.disconnect_user(1, 1);
+        let room = unwrap!(rooms.rooms.get(&room_code));
+        assert_eq!(room.host_ws_uid, 3);
+        assert_eq!(room.lobby.first(), Some(&3));
+    }
+
+    #[test]
+    fn change_lobby_reorders_and_kicks() {
+        let mut rooms = new_rooms(3);
+        let room_code = rooms.create_room(1);
+        unwrap!(rooms.room_of_user_mut(1)).invite = Some(invite(1));
+        for ws_uid in 2..=3 {
+            assert!(rooms.join_room(ws_uid, &room_code));
+            assert!(rooms.accept_invite(ws_uid).is_ok());
+        }
+        assert_eq!(
+            unwrap!(rooms.change_lobby(1, vec![1, 3, 2]).ok()),
+            Vec::<usize>::new()
+        );
+        assert_eq!(unwrap!(rooms.room_of_user(1)).lobby, vec![1, 3, 2]);
+        assert_eq!(unwrap!(rooms.change_lobby(1, vec![1, 2]).ok()), vec![3]);
+        assert_eq!(unwrap!(rooms.room_of_user(1)).lobby, vec![1, 2]);
+    }
+
+    #[test]
+    fn change_lobby_rejects_invalid_lobbies() {
+        let mut rooms = new_rooms(4);
+        let room_code = rooms.create_room(1);
+        unwrap!(rooms.room_of_user_mut(1)).invite = Some(invite(1));
+        for ws_uid in 2..=3 {
+            assert!(rooms.join_room(ws_uid, &room_code));
+            assert!(rooms.accept_invite(ws_uid).is_ok());
+        }
+        let invalid_data = ProtocolErrorCode::InvalidData.as_ref();
+        //the host must stay the first
+        assert_eq!(lobby_error(rooms.change_lobby(1, vec![2, 1])), invalid_data);
+        //only the players in the lobby
+        assert_eq!(lobby_error(rooms.change_lobby(1, vec![1, 4])), invalid_data);
+        //no duplicates
+        assert_eq!(
+            lobby_error(rooms.change_lobby(1, vec![1, 2, 2])),
+            invalid_data
+        );
+        assert_eq!(
+            lobby_error(rooms.change_lobby(2, vec![2, 1])),
+            ProtocolErrorCode::NotHost.as_ref()
+        );
+        assert_eq!(
+            lobby_error(rooms.change_lobby(4, vec![4])),
+            ProtocolErrorCode::NotInRoom.as_ref()
+        );
+        assert_eq!(unwrap!(rooms.room_of_user(1)).lobby, vec![1, 2, 3]);
+    }
+
+    ///the host 1 invites and the members 2 and 3 accept
+    fn new_rooms_with_invite() -> RoomsAndUsers {
+        let mut rooms = new_rooms(3);
+        let room_code = rooms.create_room(1);
+        unwrap!(rooms.room_of_user_mut(1)).invite = Some(invite(1));
+        for ws_uid in 2..=3 {
+            assert!(rooms.join_room(ws_uid, &room_code));
+            assert!(rooms.accept_invite(ws_uid).is_ok());
+        }
+        rooms
+    }
+
+    #[test]
+    fn accept_invite_needs_a_pending_invite() {
+        let mut rooms = new_rooms(2);
+        let room_code = rooms.create_room(1);
+        assert!(rooms.join_room(2, &room_code));
+        assert!(rooms.accept_invite(2).is_err());
+        assert_eq!(unwrap!(rooms.room_of_user(1)).lobby, vec![1]);
+        unwrap!(rooms.room_of_user_mut(1)).invite = Some(invite(1));
+        assert!(rooms.accept_invite(2).is_ok());
+        assert_eq!(unwrap!(rooms.room_of_user(1)).lobby, vec![1, 2]);
+    }
+
+    #[test]
+    fn decline_invite_leaves_the_lobby() {
+        let mut rooms = new_rooms_with_invite();
+        assert!(rooms.decline_invite(2, &WireMsg::new(WsMessage::InviteDecline { my_ws_uid: 2 })));
+        assert_eq!(unwrap!(rooms.room_of_user(1)).lobby, vec![1, 3]);
+        rooms.leave_room(3);
+        assert!(!rooms.decline_invite(3, &WireMsg::new(WsMessage::InviteDecline { my_ws_uid: 3 })));
+    }
+
+    #[test]
+    fn cancel_invite_only_by_the_host() {
+        let mut rooms = new_rooms_with_invite();
+        assert!(rooms.cancel_invite(2).is_err());
+        assert!(unwrap!(rooms.room_of_user(1)).invite.is_some());
+        assert!(rooms.cancel_invite(1).is_ok());
+        let room = unwrap!(rooms.room_of_user(1));
+        assert!(room.invite.is_none());
+        assert_eq!(room.lobby, vec![1]);
+    }
+
+    #[test]
+    fn expire_invites_after_the_invite_timeout() {
+        let mut rooms = new_rooms_with_invite();
+        rooms.expire_invites(Duration::from_secs(60));
+        assert!(unwrap!(rooms.room_of_user(1)).invite.is_some());
+        rooms.expire_invites(Duration::from_secs(0));
+        let room = unwrap!(rooms.room_of_user(1));
+        assert!(room.invite.is_none());
+        assert_eq!(room.lobby, vec![1]);
+    }
+
+    ///the text msgs already sent to this receiver
+    fn received(mut rx: mpsc::UnboundedReceiver<Message>) -> Vec<String> {
+        rx.close();
+        rx.wait()
+            .filter_map(Result::ok)
+            .filter_map(|msg| msg.to_str().ok().map(String::from))
+            .collect()
+    }
+
+    ///the user 1 is the only player of the game in his room and has sent count game msgs
+    fn new_rooms_with_game_msgs(count: usize) -> RoomsAndUsers {
+        let mut rooms = new_rooms(1);
+        rooms.create_room(1);
+        unwrap!(rooms.room_of_user_mut(1)).game =
+            Some(GameState::new(Vec::new(), vec![Player::new(1)]));
+        for _ in 0..count {
+            let msg = gamestate::snapshot(&GameState::new(Vec::new(), Vec::new()), 0);
+            rooms.send_game_msg(1, msg);
+        }
+        rooms
+    }
+
+    #[test]
+    fn is_duplicate_msg_id() {
+        let mut rooms = new_rooms(1);
+        assert!(!rooms.is_duplicate_msg_id(1, 1));
+        rooms.record_msg_id(1, 1);
+        assert!(rooms.is_duplicate_msg_id(1, 1));
+        assert!(!rooms.is_duplicate_msg_id(1, 3));
+        rooms.record_msg_id(1, 3);
+        assert!(rooms.is_duplicate_msg_id(1, 2));
+        //a msg without msg_id is never a duplicate
+        rooms.record_msg_id(1, 0);
+        assert!(!rooms.is_duplicate_msg_id(1, 0));
+        assert!(rooms.is_duplicate_msg_id(1, 3));
+    }
+
+    #[test]
+    fn rejected_msg_id_is_not_recorded() {
+        let mut rooms = new_rooms(1);
+        rooms.record_msg_id(1, 1);
+        //msg_id 2 was rejected and never recorded, the corrected msg can use it again
+        assert!(!rooms.is_duplicate_msg_id(1, 2));
+        assert!(!rooms.is_duplicate_msg_id(1, 2));
+        rooms.record_msg_id(1, 2);
+        assert!(rooms.is_duplicate_msg_id(1, 2));
+    }
+
+    #[test]
+    fn send_game_msg_keeps_the_last_msgs() {
+        let count = unwrap!(GAME_MSG_HISTORY.checked_add(2));
+        let rooms = new_rooms_with_game_msgs(count);
+        let room = unwrap!(rooms.room_of_user(1));
+        assert_eq!(room.game_seq, count);
+        assert_eq!(room.game_msgs.len(), GAME_MSG_HISTORY);
+        assert_eq!(room.game_msgs.front().map(|(seq, _j)| *seq), Some(3));
+        assert_eq!(room.game_msgs.back().map(|(seq, _j)| *seq), Some(count));
+    }
+
+    #[test]
+    fn resend_game_msgs_from_the_history() {
+        let mut rooms = new_rooms_with_game_msgs(3);
+        let (tx, rx) = mpsc::unbounded();
+        let session_token = rooms.session_token_of_user(1);
+        rooms.connect_user(&session_token, tx);
+        rooms.resend_game_msgs(1, 2);
+        let expected: Vec<String> = unwrap!(rooms.room_of_user(1))
+            .game_msgs
+            .iter()
+            .skip(1)
+            .map(|(_seq, wire_msg)| wire_msg.json())
+            .collect();
+        assert_eq!(received(rx), expected);
+    }
+
+    #[test]
+    fn resend_game_msgs_out_of_the_history_is_a_snapshot() {
+        let mut rooms = new_rooms_with_game_msgs(unwrap!(GAME_MSG_HISTORY.checked_add(2)));
+        let (tx, rx) = mpsc::unbounded();
+        let session_token = rooms.session_token_of_user(1);
+        rooms.connect_user(&session_token, tx);
+        rooms.resend_game_msgs(1, 1);
+        let msgs = received(rx);
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(
+            WsMessage::msg_type_of_json(unwrap!(msgs.first())),
+            "StateSnapshot"
+        );
+    }
+
+    #[test]
+    fn set_profile_drops_the_invalid_values() {
+        let mut rooms = new_rooms(1);
+        rooms.create_room(1);
+        unwrap!(rooms.room_of_user_mut(1)).avatars = vec!["content/alphabet/img/a.png".to_string()];
+        let mut profile = Player::new(1);
+        profile.name = "a name longer than the max".to_string();
+        profile.color = "red;background-image:url(x)".to_string();
+        profile.avatar = "https://example.com/a.png".to_string();
+        let valid_profile = rooms.set_profile(&profile);
+        assert_eq!(valid_profile.name.chars().count(), PLAYER_NAME_MAX_CHARS);
+        assert_eq!(valid_profile.color, "");
+        assert_eq!(valid_profile.avatar, "");
+        assert_eq!(unwrap!(rooms.profile(1)).color, "");
+
+        profile.name = "Ana".to_string();
+        profile.color = unwrap!(PLAYER_COLORS.first()).to_string();
+        profile.avatar = "content/alphabet/img/a.png".to_string();
+        let valid_profile = rooms.set_profile(&profile);
+        assert_eq!(valid_profile.name, profile.name);
+        assert_eq!(valid_profile.color, profile.color);
+        assert_eq!(valid_profile.avatar, profile.avatar);
+    }
+}
@@ -0,0 +1,71 @@
+//! wiremsg.rs - the msg in the wire format of every connection.
+//! The json goes in text frames, the bincode in binary frames.
+//! A msg for many receivers is serialized only once for every wire format.
+//! A received msg keeps the frame of the sender, so forwarding it in the same format needs no serialization.
+
+//region: use
+use mem4_common::WsMessage;
+use serde_json;
+use std::cell::RefCell;
+//endregion
+
+///the msg with its json and bincode. They are made on the first use and reused for all the receivers.
+#[derive(Clone)]
+pub struct WireMsg {
+    ///the msg
+    msg: WsMessage,
+    ///json for the text frames
+    json: RefCell<Option<String>>,
+    ///bincode for the binary frames
+    binary: RefCell<Option<Vec<u8>>>,
+}
+
+impl WireMsg {
+    ///the msg from the server, not yet serialized
+    pub fn new(msg: WsMessage) -> Self {
+        WireMsg {
+            msg,
+            json: RefCell::new(None),
+            binary: RefCell::new(None),
+        }
+    }
+
+    ///the msg received in a text frame with its json
+    pub fn from_json(msg: WsMessage, json: String) -> Self {
+        WireMsg {
+            msg,
+            json: RefCell::new(Some(json)),
+            binary: RefCell::new(None),
+        }
+    }
+
+    ///the msg received in a binary frame with its bincode
+    pub fn from_binary(msg: WsMessage, binary: Vec<u8>) -> Self {
+        WireMsg {
+            msg,
+            json: RefCell::new(None),
+            binary: RefCell::new(Some(binary)),
+        }
+    }
+
+    ///the msg
+    pub fn msg(&self) -> &WsMessage {
+        &self.msg
+    }
+
+    ///the json for a text frame, serialized only the first time
+    pub fn json(&self) -> String {
+        self.json
+            .borrow_mut()
+            .get_or_insert_with(|| unwrap!(serde_json::to_string(&self.msg)))
+            .clone()
+    }
+
+    ///the bincode for a binary frame, serialized only the first time
+    pub fn binary(&self) -> Vec<u8> {
+        self.binary
+            .borrow_mut()
+            .get_or_insert_with(|| self.msg.to_binary())
+            .clone()
+    }
+}